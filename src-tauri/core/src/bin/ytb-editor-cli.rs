@@ -0,0 +1,633 @@
+//! Headless version of the clip pipeline, for scripts and build boxes.
+//!
+//! Every command prints a single JSON object on stdout: `{"ok": true, ...}`
+//! on success, `{"ok": false, "error": {...}}` on failure (same error shape
+//! as the app's commands) with a non-zero exit status. `check` reports
+//! `"ok": false` with the environment report when something is missing.
+//! With `--progress`, ffmpeg progress is written to stderr as one JSON object
+//! per line.
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use ytb_editor_core::error::{Error, Result};
+use ytb_editor_core::ffmpeg::Progress;
+use ytb_editor_core::storage::{Dimensions, Source, Timing, Workspace};
+use ytb_editor_core::toolchain::{self, Overrides};
+use ytb_editor_core::transcription::chunking;
+use ytb_editor_core::transcription::glossary::Term;
+use ytb_editor_core::transcription::{self, Backend, BackendConfig, Options};
+use ytb_editor_core::translation::{self, ChatConfig};
+use ytb_editor_core::{diarization, download, media, probe, subtitles, Observer};
+
+const USAGE: &str = "\
+Usage: ytb-editor-cli [--workspace DIR] [--ffmpeg PATH] [--ffprobe PATH]
+                      [--progress] <command> [args]
+
+Commands:
+  check                               Report the ffmpeg/ffprobe versions and features
+  probe <file>                        Report the container and streams of a media file
+  download <url>                      Download a YouTube video
+  import <file>                       Import a local mp4, webm or mkv video
+  trim <id> --timing START-END...     Cut the video, timings in seconds
+            [--crop W,H,X,Y]          Crop rectangle in percent
+  transcribe <id>                     Transcribe and write every subtitle variant
+            [--language LANG]         ISO-639-1 code, detected when missing or auto
+            [--api-key KEY]           Defaults to $OPENAI_API_KEY
+            [--backend JSON]          Backend config, defaults to the project's
+            [--chunk-mb MB]           Split audio bigger than this on silence
+            [--concurrency N]         Chunks transcribed at once (default: 1)
+            [--no-cache]              Transcribe again even if a response is cached
+            [--glossary FILE]         JSON list of terms, added to the project's
+  regenerate <id>                     Rebuild the subtitles from the cached transcription
+            [--glossary FILE]         JSON list of terms, added to the project's
+  import-transcript <id> <file>       Build the subtitles from SRT, VTT, SBV or verbose_json
+            [--language LANG]         Defaults to the transcript's, if it has one
+            [--glossary FILE]         JSON list of terms, added to the project's
+  retranscribe <id> --range START-END Transcribe a range again and splice it in
+            [--language LANG]         Defaults to the project's language
+            [--api-key KEY]           Defaults to $OPENAI_API_KEY
+            [--backend JSON]          Backend config, defaults to the project's
+            [--glossary FILE]         JSON list of terms, added to the project's
+  translate <id> --to LANG...         Translate the subtitles, one track per language
+            [--api-key KEY]           Defaults to $OPENAI_API_KEY
+            [--backend JSON]          Translates to English, defaults to the project's
+            [--chat JSON]             Chat endpoint for the rest, defaults to OpenAI
+  diarize <id> --diarizer JSON        Label the cues with the speakers a diarizer finds
+          | --import FILE             or those of an RTTM or JSON file of turns
+  rename-speaker <id> LABEL NAME      Rename a speaker in the subtitles
+  burn <id> [--subtitles VARIANT]     Burn subtitles in (default: 3words)
+            [--height PX]             Defaults to the trimmed video height
+  run <job.json | ->                  Run the whole pipeline from a job file
+
+The workspace defaults to $YTB_EDITOR_WORKSPACE, then ./workspace.";
+
+const API_KEY_ENV: &str = "OPENAI_API_KEY";
+const DEFAULT_VARIANT: &str = "3words";
+/// `--language` value asking for detection.
+const AUTO_LANGUAGE: &str = "auto";
+
+/// Input of the `run` command.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct JobFile {
+    source: Source,
+    /// Defaults to the whole video.
+    #[serde(default)]
+    timings: Vec<Timing>,
+    crop: Option<Dimensions>,
+    /// Transcription is skipped when no language is given, "auto" detects it.
+    language: Option<String>,
+    api_key: Option<String>,
+    /// Defaults to the project's backend, then OpenAI.
+    backend: Option<BackendConfig>,
+    /// Added to the project's glossary.
+    #[serde(default)]
+    glossary: Vec<Term>,
+    /// Subtitle variant to burn, nothing is burned when missing.
+    burn: Option<String>,
+    video_height: Option<i32>,
+}
+
+struct CliObserver {
+    progress: bool,
+}
+
+impl Observer for CliObserver {
+    fn progress(&self, stage: &str, progress: &Progress) {
+        if self.progress {
+            eprintln!("{}", json!({ "stage": stage, "progress": progress }));
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match run(args).await {
+        Ok(mut output) => {
+            let ok = output["ok"].as_bool().unwrap_or(true);
+            output["ok"] = json!(ok);
+            println!("{}", output);
+            if ok {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            println!("{}", json!({ "ok": false, "error": e }));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Vec<String>) -> Result<Value> {
+    let mut args = Args::new(args);
+    let mut workspace_dir = None;
+    let mut overrides = Overrides::default();
+    let mut progress = false;
+    let command = loop {
+        match args.next_required("command")?.as_str() {
+            "--workspace" => workspace_dir = Some(PathBuf::from(args.next_required("--workspace")?)),
+            "--ffmpeg" => overrides.ffmpeg = Some(PathBuf::from(args.next_required("--ffmpeg")?)),
+            "--ffprobe" => overrides.ffprobe = Some(PathBuf::from(args.next_required("--ffprobe")?)),
+            "--progress" => progress = true,
+            command => break command.to_string(),
+        }
+    };
+
+    toolchain::configure(overrides);
+    if command == "check" {
+        args.finish()?;
+        let report = toolchain::check().await;
+        let problems = report.problems(&toolchain::Feature::ALL);
+        return Ok(json!({ "ok": report.ok, "environment": report, "problems": problems }));
+    }
+    if command == "probe" {
+        let file = args.next_required("file")?;
+        args.finish()?;
+        let info = probe::probe(Path::new(&file)).await?;
+        return Ok(json!({ "media": info }));
+    }
+
+    let workspace = Workspace::resolve(workspace_dir.as_deref(), Path::new("workspace"))?;
+    let observer = CliObserver { progress };
+
+    match command.as_str() {
+        "download" => {
+            let url = args.next_required("url")?;
+            args.finish()?;
+            let video_id = download::download(&workspace, &url, &observer).await?;
+            video_output(&workspace, &video_id)
+        }
+        "import" => {
+            let file = args.next_required("file")?;
+            args.finish()?;
+            let video_id = media::import(&workspace, &file, &observer).await?;
+            video_output(&workspace, &video_id)
+        }
+        "trim" => {
+            let video_id = args.next_required("video id")?;
+            let mut timings = Vec::new();
+            let mut crop = None;
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--timing" => timings.push(parse_timing(&args.next_required("--timing")?)?),
+                    "--crop" => crop = Some(parse_crop(&args.next_required("--crop")?)?),
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            trim(&workspace, &video_id, timings, crop, &observer).await
+        }
+        "transcribe" => {
+            let video_id = args.next_required("video id")?;
+            let mut language = None;
+            let mut api_key = None;
+            let mut backend = None;
+            let mut max_chunk_bytes = None;
+            let mut concurrency = 1;
+            let mut reuse_cached = true;
+            let mut glossary = Vec::new();
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--language" => language = Some(args.next_required("--language")?),
+                    "--api-key" => api_key = Some(args.next_required("--api-key")?),
+                    "--backend" => backend = Some(parse_json("--backend", &args.next_required("--backend")?)?),
+                    "--chunk-mb" => {
+                        let megabytes: f64 = parse_number(&args.next_required("--chunk-mb")?)?;
+                        // negative and NaN sizes become 0, which is refused
+                        let bytes = (megabytes * 1024.0 * 1024.0) as u64;
+                        max_chunk_bytes = Some(chunking::check_max_bytes(bytes)?);
+                    }
+                    "--concurrency" => {
+                        concurrency = parse_number(&args.next_required("--concurrency")?)?
+                    }
+                    "--no-cache" => reuse_cached = false,
+                    "--glossary" => glossary = read_glossary(&args.next_required("--glossary")?)?,
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            let options = Options {
+                language: language.filter(|language| language != AUTO_LANGUAGE),
+                max_chunk_bytes,
+                concurrency,
+                reuse_cached,
+                glossary,
+            };
+            transcribe(&workspace, &video_id, &options, backend, api_key, &observer).await
+        }
+        "regenerate" => {
+            let video_id = args.next_required("video id")?;
+            let mut glossary = Vec::new();
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--glossary" => glossary = read_glossary(&args.next_required("--glossary")?)?,
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            transcription::regenerate(&workspace, &video_id, &glossary)?;
+            subtitles_output(&workspace, &video_id)
+        }
+        "import-transcript" => {
+            let video_id = args.next_required("video id")?;
+            let file = PathBuf::from(args.next_required("transcript file")?);
+            let mut language = None;
+            let mut glossary = Vec::new();
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--language" => language = Some(args.next_required("--language")?),
+                    "--glossary" => glossary = read_glossary(&args.next_required("--glossary")?)?,
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            let outcome = transcription::import_transcript(
+                &workspace,
+                &video_id,
+                &file,
+                language.as_deref(),
+                &glossary,
+            )?;
+            let mut output = subtitles_output(&workspace, &video_id)?;
+            output["language"] = json!(outcome.language);
+            Ok(output)
+        }
+        "retranscribe" => {
+            let video_id = args.next_required("video id")?;
+            let mut range = None;
+            let mut language = None;
+            let mut api_key = None;
+            let mut backend = None;
+            let mut glossary = Vec::new();
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--range" => range = Some(parse_timing(&args.next_required("--range")?)?),
+                    "--language" => language = Some(args.next_required("--language")?),
+                    "--api-key" => api_key = Some(args.next_required("--api-key")?),
+                    "--backend" => backend = Some(parse_json("--backend", &args.next_required("--backend")?)?),
+                    "--glossary" => glossary = read_glossary(&args.next_required("--glossary")?)?,
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            let Some(range) = range else {
+                return Err(Error::InvalidInput("Missing --range, see --help".to_string()));
+            };
+            let options = Options {
+                language,
+                glossary,
+                ..Options::default()
+            };
+            let backend = build_backend(&workspace, &video_id, backend, api_key)?;
+            transcription::retranscribe_range(
+                &workspace,
+                &video_id,
+                backend.as_ref(),
+                range.start as f64,
+                range.end as f64,
+                &options,
+                &observer,
+            )
+            .await?;
+            subtitles_output(&workspace, &video_id)
+        }
+        "translate" => {
+            let video_id = args.next_required("video id")?;
+            let mut languages = Vec::new();
+            let mut api_key = None;
+            let mut backend = None;
+            let mut chat = ChatConfig::default();
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--to" => languages.push(args.next_required("--to")?),
+                    "--api-key" => api_key = Some(args.next_required("--api-key")?),
+                    "--backend" => backend = Some(parse_json("--backend", &args.next_required("--backend")?)?),
+                    "--chat" => chat = parse_json("--chat", &args.next_required("--chat")?)?,
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            if languages.is_empty() {
+                return Err(Error::InvalidInput("Missing --to, see --help".to_string()));
+            }
+            translate(&workspace, &video_id, &languages, backend, &chat, api_key, &observer).await
+        }
+        "diarize" => {
+            let video_id = args.next_required("video id")?;
+            let mut diarizer = None;
+            let mut import = None;
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--diarizer" => diarizer = Some(parse_json("--diarizer", &args.next_required("--diarizer")?)?),
+                    "--import" => import = Some(PathBuf::from(args.next_required("--import")?)),
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            let speakers = match (diarizer, import) {
+                (Some(diarizer), None) => {
+                    diarization::diarize(&workspace, &video_id, &diarizer, &observer).await?
+                }
+                (None, Some(file)) => diarization::import(&workspace, &video_id, &file)?,
+                _ => {
+                    return Err(Error::InvalidInput(
+                        "Expected either --diarizer or --import, see --help".to_string(),
+                    ))
+                }
+            };
+            Ok(json!({ "videoId": video_id, "speakers": speakers }))
+        }
+        "rename-speaker" => {
+            let video_id = args.next_required("video id")?;
+            let label = args.next_required("speaker label")?;
+            let name = args.next_required("speaker name")?;
+            args.finish()?;
+            let names = [(label, name)].into_iter().collect();
+            let speakers = diarization::rename(&workspace, &video_id, &names)?;
+            Ok(json!({ "videoId": video_id, "speakers": speakers }))
+        }
+        "burn" => {
+            let video_id = args.next_required("video id")?;
+            let mut variant = DEFAULT_VARIANT.to_string();
+            let mut height = None;
+            while let Some(flag) = args.flag() {
+                match flag.as_str() {
+                    "--subtitles" => variant = args.next_required("--subtitles")?,
+                    "--height" => height = Some(parse_number(&args.next_required("--height")?)?),
+                    _ => return Err(unexpected(&flag)),
+                }
+            }
+            burn(&workspace, &video_id, &variant, height, &observer).await
+        }
+        "run" => {
+            let file = args.next_required("job file")?;
+            args.finish()?;
+            let content = if file == "-" {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                std::fs::read_to_string(&file)?
+            };
+            let job: JobFile = serde_json::from_str(&content)
+                .map_err(|e| Error::InvalidInput(format!("Invalid job file: {}", e)))?;
+            run_job(&workspace, job, &observer).await
+        }
+        _ => Err(Error::InvalidInput(format!(
+            "Unknown command {:?}, see --help",
+            command
+        ))),
+    }
+}
+
+async fn run_job(workspace: &Workspace, job: JobFile, observer: &CliObserver) -> Result<Value> {
+    let video_id = match &job.source {
+        Source::Youtube { url } => download::download(workspace, url, observer).await?,
+        Source::Upload { path } => media::import(workspace, path, observer).await?,
+        Source::Unknown => {
+            return Err(Error::InvalidInput(
+                "Job source must be \"youtube\" or \"upload\"".to_string(),
+            ))
+        }
+    };
+
+    let mut output = video_output(workspace, &video_id)?;
+    output["trim"] = trim(workspace, &video_id, job.timings, job.crop, observer).await?;
+    if let Some(language) = job.language {
+        let options = Options {
+            language: Some(language).filter(|language| language != AUTO_LANGUAGE),
+            glossary: job.glossary,
+            ..Options::default()
+        };
+        output["transcribe"] =
+            transcribe(workspace, &video_id, &options, job.backend, job.api_key, observer).await?;
+    }
+    if let Some(variant) = &job.burn {
+        output["burn"] = burn(workspace, &video_id, variant, job.video_height, observer).await?;
+    }
+    Ok(output)
+}
+
+async fn trim(
+    workspace: &Workspace,
+    video_id: &str,
+    mut timings: Vec<Timing>,
+    crop: Option<Dimensions>,
+    observer: &CliObserver,
+) -> Result<Value> {
+    if timings.is_empty() {
+        let input = workspace.file(video_id, ".mp4")?;
+        let duration = probe::probe(&input)
+            .await?
+            .duration
+            .ok_or_else(|| Error::Ffmpeg(format!("Failed to get duration of {:?}", input)))?;
+        timings.push(Timing {
+            start: 0.0,
+            end: duration as f32,
+        });
+    }
+    let output = media::trim(workspace, video_id, timings, crop, observer).await?;
+    file_output(workspace, video_id, &output)
+}
+
+async fn transcribe(
+    workspace: &Workspace,
+    video_id: &str,
+    options: &Options,
+    backend: Option<BackendConfig>,
+    api_key: Option<String>,
+    observer: &CliObserver,
+) -> Result<Value> {
+    let backend = build_backend(workspace, video_id, backend, api_key)?;
+    let outcome =
+        transcription::transcribe(workspace, video_id, backend.as_ref(), options, observer).await?;
+    let mut output = subtitles_output(workspace, video_id)?;
+    output["language"] = json!(outcome.language);
+    output["languageDetected"] = json!(outcome.detected);
+    output["cached"] = json!(outcome.cached);
+    Ok(output)
+}
+
+/// `backend`, else the project's, with `api_key` or the one from the
+/// environment.
+fn build_backend(
+    workspace: &Workspace,
+    video_id: &str,
+    backend: Option<BackendConfig>,
+    api_key: Option<String>,
+) -> Result<Box<dyn Backend>> {
+    let backend = backend.unwrap_or_else(|| {
+        BackendConfig::for_project(workspace, video_id, BackendConfig::default())
+    });
+    let api_key = api_key
+        .or_else(|| std::env::var(API_KEY_ENV).ok())
+        .filter(|key| !key.is_empty());
+    if api_key.is_none() && matches!(backend, BackendConfig::OpenAi { .. }) {
+        return Err(Error::InvalidInput(format!(
+            "Missing --api-key and {} is not set",
+            API_KEY_ENV
+        )));
+    }
+    backend.build(api_key.as_deref())
+}
+
+/// The backend only translates to English, and is skipped when it cannot
+/// be built, e.g. without an API key.
+async fn translate(
+    workspace: &Workspace,
+    video_id: &str,
+    languages: &[String],
+    backend: Option<BackendConfig>,
+    chat: &ChatConfig,
+    api_key: Option<String>,
+    observer: &CliObserver,
+) -> Result<Value> {
+    let api_key = api_key
+        .or_else(|| std::env::var(API_KEY_ENV).ok())
+        .filter(|key| !key.is_empty());
+    let backend = backend
+        .unwrap_or_else(|| BackendConfig::for_project(workspace, video_id, BackendConfig::default()))
+        .build(api_key.as_deref())
+        .ok();
+    let tracks = translation::translate(
+        workspace,
+        video_id,
+        languages,
+        backend.as_deref(),
+        chat,
+        api_key.as_deref(),
+        observer,
+    )
+    .await?;
+
+    let mut subtitles = serde_json::Map::new();
+    for (language, variant) in tracks {
+        subtitles.insert(language, json!(workspace.subtitle_file(video_id, &variant)?));
+    }
+    Ok(json!({ "videoId": video_id, "subtitles": subtitles }))
+}
+
+async fn burn(
+    workspace: &Workspace,
+    video_id: &str,
+    variant: &str,
+    height: Option<i32>,
+    observer: &CliObserver,
+) -> Result<Value> {
+    let height = match height {
+        Some(height) => height,
+        None => {
+            let trimmed = workspace.file(video_id, "_trimmed.mp4")?;
+            probe::probe(&trimmed)
+                .await?
+                .require_video(&trimmed)?
+                .frame_size()
+                .map(|(_, height)| height as i32)
+                .ok_or_else(|| {
+                    Error::Ffmpeg(format!("Failed to get video dimensions of {:?}", trimmed))
+                })?
+        }
+    };
+    let output = media::burn(workspace, video_id, variant, height, observer).await?;
+    file_output(workspace, video_id, &output)
+}
+
+fn video_output(workspace: &Workspace, video_id: &str) -> Result<Value> {
+    Ok(json!({ "videoId": video_id, "video": workspace.file(video_id, ".mp4")? }))
+}
+
+fn subtitles_output(workspace: &Workspace, video_id: &str) -> Result<Value> {
+    let mut subtitles = serde_json::Map::new();
+    for variant in subtitles::SUBTITLE_VARIANTS {
+        let path = workspace.subtitle_file(video_id, variant)?;
+        subtitles.insert(variant.to_string(), json!(path));
+    }
+    Ok(json!({ "videoId": video_id, "subtitles": subtitles }))
+}
+
+fn file_output(workspace: &Workspace, video_id: &str, file_name: &str) -> Result<Value> {
+    let path = workspace.project_dir(video_id)?.join(file_name);
+    Ok(json!({ "videoId": video_id, "output": path }))
+}
+
+/// `START-END` in seconds, e.g. `12.5-30`.
+fn parse_timing(value: &str) -> Result<Timing> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| Error::InvalidInput(format!("Invalid timing {:?}, expected START-END", value)))?;
+    Ok(Timing {
+        start: parse_number(start)?,
+        end: parse_number(end)?,
+    })
+}
+
+/// `WIDTH,HEIGHT,X,Y` in percent of the video.
+fn parse_crop(value: &str) -> Result<Dimensions> {
+    let parts = value
+        .split(',')
+        .map(parse_number)
+        .collect::<Result<Vec<f32>>>()?;
+    match parts[..] {
+        [width, height, x, y] => Ok(Dimensions {
+            width,
+            height,
+            x,
+            y,
+        }),
+        _ => Err(Error::InvalidInput(format!(
+            "Invalid crop {:?}, expected WIDTH,HEIGHT,X,Y",
+            value
+        ))),
+    }
+}
+
+/// A JSON file holding a list of glossary terms.
+fn read_glossary(file: &str) -> Result<Vec<Term>> {
+    parse_json("--glossary", &std::fs::read_to_string(file)?)
+}
+
+fn parse_json<T: DeserializeOwned>(flag: &str, json: &str) -> Result<T> {
+    serde_json::from_str(json).map_err(|e| Error::InvalidInput(format!("Invalid {}: {}", flag, e)))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidInput(format!("Invalid number {:?}", value)))
+}
+
+fn unexpected(arg: &str) -> Error {
+    Error::InvalidInput(format!("Unexpected argument {:?}, see --help", arg))
+}
+
+struct Args {
+    args: std::vec::IntoIter<String>,
+}
+
+impl Args {
+    fn new(args: Vec<String>) -> Self {
+        Args {
+            args: args.into_iter(),
+        }
+    }
+
+    fn flag(&mut self) -> Option<String> {
+        self.args.next()
+    }
+
+    fn next_required(&mut self, name: &str) -> Result<String> {
+        self.args
+            .next()
+            .ok_or_else(|| Error::InvalidInput(format!("Missing {}, see --help", name)))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        match self.args.next() {
+            Some(arg) => Err(unexpected(&arg)),
+            None => Ok(()),
+        }
+    }
+}
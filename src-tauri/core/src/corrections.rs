@@ -0,0 +1,247 @@
+//! Corrections learned from subtitle edits.
+//!
+//! When an edited subtitle file is saved, its cues are compared with the
+//! cues generated from the cached transcription and every replaced run of
+//! words is counted in a dictionary shared by the projects of the same
+//! YouTube channel, or of the same kind of source. Substitutions made at
+//! least [`MIN_OCCURRENCES`] times are applied to later transcriptions in
+//! that scope, before the glossary. The generated cues are rebuilt without
+//! the learned corrections, so keeping one in a later project counts it again.
+//!
+//! The dictionary is `corrections.json` in the workspace root:
+//! `{scope: {misspelling: {replacement: count}}}`, misspellings normalized
+//! to lowercase words without punctuation. What each variant of a project
+//! adds to it is kept in `{id}_corrections.json`, so saving the same edits
+//! again replaces them instead of counting them twice.
+
+use crate::error::{Error, Result};
+use crate::storage::{Project, Source, Workspace};
+use crate::subtitles::{self, Cue};
+use crate::transcription::glossary::normalize;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const CORRECTIONS_FILE: &str = "corrections.json";
+/// Suffix of the substitutions counted for a project, per variant.
+const PROJECT_SUFFIX: &str = "_corrections.json";
+/// Times a substitution must have been made before it is applied.
+pub const MIN_OCCURRENCES: u32 = 2;
+/// Longer rewrites are edits of the sentence, not misheard words.
+const MAX_RUN_WORDS: usize = 3;
+/// Cues of the two versions are paired when their timings are this close.
+const TIMING_TOLERANCE: f64 = 0.0005;
+
+/// Serializes read-modify-write cycles of the dictionary.
+static DICTIONARY_LOCK: Mutex<()> = Mutex::new(());
+
+type Dictionary = BTreeMap<String, BTreeMap<String, BTreeMap<String, u32>>>;
+/// `{sub_type: [(misspelling, replacement)]}`
+type Counted = BTreeMap<String, Vec<(String, String)>>;
+
+/// Which projects share learned corrections: the videos of a YouTube
+/// channel, otherwise every project of the same kind of source.
+pub fn scope(project: &Project) -> String {
+    match (&project.channel, &project.source) {
+        (Some(channel), _) => format!("youtube:{}", channel),
+        (None, Source::Youtube { .. }) => "youtube".to_string(),
+        (None, Source::Upload { .. }) => "upload".to_string(),
+        (None, Source::Unknown) => "unknown".to_string(),
+    }
+}
+
+/// Counts the words replaced in the `edited` subtitle variant compared with
+/// the `generated` one in the project's scope, in place of what earlier
+/// saves of the variant counted, and returns how many substitutions it has.
+/// Translated tracks teach nothing about the transcript.
+pub fn learn(
+    workspace: &Workspace,
+    video_id: &str,
+    sub_type: &str,
+    generated: &str,
+    edited: &str,
+) -> Result<usize> {
+    if subtitles::is_translated_variant(sub_type) {
+        return Ok(0);
+    }
+    let substitutions = diff_cues(&subtitles::parse_cues(generated), &subtitles::parse_cues(edited));
+    let counted_path = workspace.file(video_id, PROJECT_SUFFIX)?;
+    let mut counted: Counted = read_json(&counted_path)?;
+    let previous = counted.remove(sub_type).unwrap_or_default();
+    if previous == substitutions {
+        return Ok(substitutions.len());
+    }
+    let scope = scope(&Project::load(workspace, video_id)?);
+
+    let _guard = DICTIONARY_LOCK.lock().unwrap();
+    let mut dictionary: Dictionary = read_json(&path(workspace))?;
+    let learned = dictionary.entry(scope.clone()).or_default();
+    for (misspelling, replacement) in &previous {
+        let Some(replacements) = learned.get_mut(misspelling) else {
+            continue;
+        };
+        if let Some(count) = replacements.get_mut(replacement) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                replacements.remove(replacement);
+            }
+        }
+        if replacements.is_empty() {
+            learned.remove(misspelling);
+        }
+    }
+    for (misspelling, replacement) in &substitutions {
+        *learned
+            .entry(misspelling.clone())
+            .or_default()
+            .entry(replacement.clone())
+            .or_default() += 1;
+    }
+    if learned.is_empty() {
+        dictionary.remove(&scope);
+    }
+    write_json(&path(workspace), &dictionary)?;
+
+    if !substitutions.is_empty() {
+        counted.insert(sub_type.to_string(), substitutions.clone());
+    }
+    write_json(&counted_path, &counted)?;
+    Ok(substitutions.len())
+}
+
+/// `(misspelling, replacement)` pairs to apply to a transcription of the
+/// project, the most frequent replacement of every misspelling made at
+/// least [`MIN_OCCURRENCES`] times. An unreadable dictionary is ignored.
+pub fn learned(workspace: &Workspace, video_id: &str) -> Vec<(String, String)> {
+    let Ok(project) = Project::load(workspace, video_id) else {
+        return Vec::new();
+    };
+    let dictionary = match read_json::<Dictionary>(&path(workspace)) {
+        Ok(dictionary) => dictionary,
+        Err(e) => {
+            eprintln!("Ignoring learned corrections: {}", e);
+            return Vec::new();
+        }
+    };
+    dictionary
+        .get(&scope(&project))
+        .into_iter()
+        .flatten()
+        .filter_map(|(misspelling, replacements)| {
+            replacements
+                .iter()
+                .filter(|(_, count)| **count >= MIN_OCCURRENCES)
+                .max_by_key(|(_, count)| **count)
+                .map(|(replacement, _)| (misspelling.clone(), replacement.clone()))
+        })
+        .collect()
+}
+
+fn path(workspace: &Workspace) -> PathBuf {
+    workspace.root().join(CORRECTIONS_FILE)
+}
+
+/// The JSON file at `path`, empty when it does not exist yet.
+fn read_json<T: Default + DeserializeOwned>(path: &Path) -> Result<T> {
+    if !path.is_file() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| Error::InvalidInput(format!("Invalid corrections file {:?}: {}", path, e)))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize corrections: {}", e)))?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Substitutions between the cues of `before` and `after` with the same
+/// timings. Cues that were retimed, added or removed are skipped.
+fn diff_cues(before: &[Cue], after: &[Cue]) -> Vec<(String, String)> {
+    let mut substitutions = Vec::new();
+    for cue in after {
+        let same_timing = |old: &&Cue| {
+            (old.start - cue.start).abs() < TIMING_TOLERANCE && (old.end - cue.end).abs() < TIMING_TOLERANCE
+        };
+        if let Some(old) = before.iter().find(same_timing) {
+            let (old_text, text) = (subtitles::strip_voice(&old.text), subtitles::strip_voice(&cue.text));
+            if old_text != text {
+                substitutions.extend(diff_words(old_text, text));
+            }
+        }
+    }
+    substitutions
+}
+
+/// Runs of words replaced between `before` and `after`, as normalized
+/// misspelling and replacement without surrounding punctuation. Insertions,
+/// deletions, long rewrites and case changes are not substitutions.
+fn diff_words(before: &str, after: &str) -> Vec<(String, String)> {
+    let old: Vec<&str> = before.split_whitespace().collect();
+    let new: Vec<&str> = after.split_whitespace().collect();
+    let old_keys: Vec<String> = old.iter().map(|word| normalize(word)).collect();
+    let new_keys: Vec<String> = new.iter().map(|word| normalize(word)).collect();
+
+    // longest common subsequence, lengths of the suffixes
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old_keys[i] == new_keys[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut substitutions = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut run_i, mut run_j) = (0, 0);
+    loop {
+        let at_end = i == old.len() && j == new.len();
+        let matched = !at_end && i < old.len() && j < new.len() && old_keys[i] == new_keys[j];
+        if at_end || matched {
+            let replaced = &old_keys[run_i..i];
+            let replacement = &new[run_j..j];
+            if let Some(substitution) = substitution(replaced, replacement) {
+                substitutions.push(substitution);
+            }
+            if at_end {
+                break;
+            }
+            i += 1;
+            j += 1;
+            (run_i, run_j) = (i, j);
+        } else if j == new.len() || (i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    substitutions
+}
+
+fn substitution(replaced: &[String], replacement: &[&str]) -> Option<(String, String)> {
+    // words that were only punctuation
+    let replaced: Vec<&str> = replaced.iter().map(String::as_str).filter(|word| !word.is_empty()).collect();
+    if replaced.is_empty() || replacement.is_empty() {
+        return None;
+    }
+    if replaced.len() > MAX_RUN_WORDS || replacement.len() > MAX_RUN_WORDS {
+        return None;
+    }
+    let misspelling = replaced.join(" ");
+    let replacement = replacement
+        .join(" ")
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_string();
+    if replacement.is_empty() || normalize(&replacement) == misspelling {
+        return None;
+    }
+    Some((misspelling, replacement))
+}
@@ -0,0 +1,357 @@
+//! Who speaks when.
+//!
+//! Speaker turns come from a diarizer run on the project's audio, an
+//! external program or pyannote models run in-process, see
+//! [`DiarizerConfig`], or from an RTTM or JSON file made by another tool. They are kept in `{id}_speakers.json` with a hash of the
+//! audio, and every cue of the subtitles gets the speaker talking the longest
+//! during it as a WebVTT voice span, `<v Speaker 1>`. Speakers are named
+//! "Speaker N" in order of appearance until renamed, the names are stored in
+//! the manifest.
+
+#[cfg(feature = "diarization")]
+pub mod pyannote;
+
+use crate::error::{Error, Result};
+use crate::runner;
+use crate::storage::{Project, Workspace};
+use crate::subtitles;
+use crate::toolchain::{self, Feature};
+use crate::Observer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(feature = "diarization")]
+pub use pyannote::PyannoteDiarizer;
+
+/// What [`diarize`] needs from ffmpeg: no encoder, the audio is at most
+/// decoded to samples.
+pub const DIARIZE_FEATURES: &[Feature] = &[];
+/// A cue outside every turn gets the speaker of a turn this close, in seconds.
+const MAX_GAP: f64 = 1.0;
+
+/// One speaker talking from `start` to `end`, in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Turn {
+    pub start: f64,
+    pub end: f64,
+    /// Label given by the diarizer, e.g. `SPEAKER_00`.
+    pub speaker: String,
+}
+
+/// Content of `{id}_speakers.json`.
+#[derive(Serialize, Deserialize)]
+struct Speakers {
+    /// SHA-256 of the mp3 the turns were found in.
+    audio: String,
+    turns: Vec<Turn>,
+}
+
+/// How a project's audio is diarized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum DiarizerConfig {
+    /// An external program, e.g. a pyannote script running a model on the
+    /// CPU. `{audio}` in `args` is replaced by the mp3 path. The program must
+    /// print RTTM or JSON turns on stdout, see [`parse`], and exit with
+    /// status 0.
+    Command {
+        program: PathBuf,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// pyannote's segmentation model and a WeSpeaker embedding model, as
+    /// ONNX files, run in-process, see `PyannoteDiarizer`. Only available in
+    /// builds with the `diarization` feature.
+    Pyannote {
+        segmentation_model: PathBuf,
+        embedding_model: PathBuf,
+        /// Cosine similarity from which two voices are the same speaker,
+        /// 0.5 when missing. Higher finds more speakers.
+        #[serde(default)]
+        threshold: Option<f32>,
+    },
+}
+
+impl DiarizerConfig {
+    /// Speaker turns of the mp3 `audio`.
+    pub async fn run(&self, audio: &Path, observer: &dyn Observer) -> Result<Vec<Turn>> {
+        match self {
+            DiarizerConfig::Command { program, args } => {
+                let audio = audio.to_string_lossy();
+                let mut command = Command::new(program);
+                for arg in args {
+                    command.arg(arg.replace("{audio}", &audio));
+                }
+                let output = runner::run_program(command, None, Error::Diarization).await?;
+                parse(&String::from_utf8_lossy(&output.stdout)).map_err(|e| {
+                    Error::Diarization(format!("{:?} did not print speaker turns: {}", program, e))
+                })
+            }
+            #[cfg(feature = "diarization")]
+            DiarizerConfig::Pyannote {
+                segmentation_model,
+                embedding_model,
+                threshold,
+            } => {
+                PyannoteDiarizer::new(segmentation_model.clone(), embedding_model.clone(), *threshold)?
+                    .run(audio, observer)
+                    .await
+            }
+            #[cfg(not(feature = "diarization"))]
+            DiarizerConfig::Pyannote { .. } => {
+                let _ = observer;
+                Err(Error::Environment(
+                    "This build has no offline diarization, rebuild with the `diarization` feature"
+                        .to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Diarizes `{id}.mp3` and labels the subtitles, reporting the progress of
+/// an in-process diarizer to `observer`. Returns the speaker names by label.
+pub async fn diarize(
+    workspace: &Workspace,
+    video_id: &str,
+    diarizer: &DiarizerConfig,
+    observer: &dyn Observer,
+) -> Result<BTreeMap<String, String>> {
+    toolchain::require(DIARIZE_FEATURES).await?;
+    let audio = audio(workspace, video_id)?;
+    eprintln!("Diarizing audio...");
+    let turns = diarizer.run(&audio, observer).await?;
+    save(workspace, video_id, &audio, turns)
+}
+
+/// Reads the speaker turns of `{id}.mp3` from an RTTM or JSON file made by
+/// another tool, and labels the subtitles. Returns the speaker names by
+/// label.
+pub fn import(workspace: &Workspace, video_id: &str, file: &Path) -> Result<BTreeMap<String, String>> {
+    let audio = audio(workspace, video_id)?;
+    let turns = parse(&fs::read_to_string(file)?)
+        .map_err(|e| Error::InvalidInput(format!("Invalid speaker file {:?}: {}", file, e)))?;
+    save(workspace, video_id, &audio, turns)
+}
+
+/// Renames speakers, by label, and labels the subtitles again. Returns every
+/// speaker name by label.
+pub fn rename(
+    workspace: &Workspace,
+    video_id: &str,
+    names: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>> {
+    let known = Project::load(workspace, video_id)?.speakers;
+    for (label, name) in names {
+        if !known.contains_key(label) {
+            return Err(Error::NotFound(format!("Project {} has no speaker {}", video_id, label)));
+        }
+        // the name ends the voice span, and spans are on one line
+        if name.trim().is_empty() || name.contains(['<', '>', '\n', '\r']) {
+            return Err(Error::InvalidInput(format!("Invalid speaker name {:?}", name)));
+        }
+    }
+    let project = Project::update(workspace, video_id, |project| {
+        for (label, name) in names {
+            project.speakers.insert(label.clone(), name.trim().to_string());
+        }
+    })?;
+    label(workspace, video_id)?;
+    Ok(project.speakers)
+}
+
+/// Puts the speaker of every cue of the subtitle variants and translated
+/// tracks in a voice span. Does nothing when the project was not diarized,
+/// or its audio changed since.
+pub fn label(workspace: &Workspace, video_id: &str) -> Result<()> {
+    let path = path(workspace, video_id)?;
+    if !path.is_file() {
+        return Ok(());
+    }
+    let speakers: Speakers = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| Error::InvalidInput(format!("Invalid speaker file {:?}: {}", path, e)))?;
+    let audio = workspace.file(video_id, ".mp3")?;
+    if !audio.is_file() || fingerprint(&audio)? != speakers.audio {
+        eprintln!("Ignoring the speakers of project {}, its audio changed since", video_id);
+        return Ok(());
+    }
+
+    let project = Project::load(workspace, video_id)?;
+    let translations = project
+        .transcription
+        .map(|settings| settings.translations)
+        .unwrap_or_default();
+    let variants = subtitles::SUBTITLE_VARIANTS
+        .iter()
+        .map(|variant| variant.to_string())
+        .chain(translations.iter().map(|language| subtitles::translated_variant(language)));
+    for variant in variants {
+        let file = workspace.subtitle_file(video_id, &variant)?;
+        if !file.is_file() {
+            continue;
+        }
+        let labelled = subtitles::map_cues(&fs::read_to_string(&file)?, |cue| {
+            match speaker_of(&speakers.turns, cue.start, cue.end)
+                .and_then(|speaker| project.speakers.get(speaker))
+            {
+                Some(name) => subtitles::with_voice(&cue.text, name),
+                None => subtitles::strip_voice(&cue.text).to_string(),
+            }
+        });
+        fs::write(&file, labelled)?;
+    }
+    Ok(())
+}
+
+/// Speaker turns from RTTM, or from JSON: a list of `{start, end, speaker}`
+/// or an object with such `segments`, as printed by pyannote and WhisperX.
+/// `label` is accepted for `speaker`, entries without one are skipped.
+pub fn parse(content: &str) -> Result<Vec<Turn>, String> {
+    let mut turns = if content.trim_start().starts_with(['[', '{']) {
+        parse_json(content)?
+    } else {
+        parse_rttm(content)?
+    };
+    turns.retain(|turn| turn.end > turn.start);
+    if turns.is_empty() {
+        return Err("no speaker turns".to_string());
+    }
+    turns.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(turns)
+}
+
+fn parse_json(content: &str) -> Result<Vec<Turn>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let items = match &value {
+        Value::Array(items) => items,
+        _ => value["segments"]
+            .as_array()
+            .ok_or("expected a list of turns or an object with `segments`")?,
+    };
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let speaker = item["speaker"].as_str().or(item["label"].as_str())?;
+            Some(Turn {
+                start: item["start"].as_f64()?,
+                end: item["end"].as_f64()?,
+                speaker: speaker.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// `SPEAKER file channel start duration <NA> <NA> label <NA> <NA>` lines.
+fn parse_rttm(content: &str) -> Result<Vec<Turn>, String> {
+    let mut turns = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() != Some(&"SPEAKER") {
+            continue;
+        }
+        let number = |i: usize| fields.get(i).and_then(|field| field.parse::<f64>().ok());
+        let (Some(start), Some(duration), Some(speaker)) = (number(3), number(4), fields.get(7)) else {
+            return Err(format!("invalid RTTM line {:?}", line));
+        };
+        turns.push(Turn {
+            start,
+            end: start + duration,
+            speaker: speaker.to_string(),
+        });
+    }
+    Ok(turns)
+}
+
+/// Label of the speaker talking the longest between `start` and `end`, else
+/// of the closest turn within [`MAX_GAP`].
+fn speaker_of(turns: &[Turn], start: f64, end: f64) -> Option<&str> {
+    let mut talking: BTreeMap<&str, f64> = BTreeMap::new();
+    for turn in turns {
+        let overlap = turn.end.min(end) - turn.start.max(start);
+        if overlap > 0.0 {
+            *talking.entry(&turn.speaker).or_default() += overlap;
+        }
+    }
+    if let Some((speaker, _)) = talking.into_iter().max_by(|a, b| a.1.total_cmp(&b.1)) {
+        return Some(speaker);
+    }
+    let middle = (start + end) / 2.0;
+    turns
+        .iter()
+        .map(|turn| (turn, (turn.start - middle).max(middle - turn.end)))
+        .filter(|(_, gap)| *gap <= MAX_GAP)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(turn, _)| turn.speaker.as_str())
+}
+
+/// Stores the turns, names the new speakers and labels the subtitles.
+fn save(
+    workspace: &Workspace,
+    video_id: &str,
+    audio: &Path,
+    turns: Vec<Turn>,
+) -> Result<BTreeMap<String, String>> {
+    let mut labels: Vec<&str> = Vec::new();
+    for turn in &turns {
+        if !labels.contains(&turn.speaker.as_str()) {
+            labels.push(&turn.speaker);
+        }
+    }
+    let project = Project::update(workspace, video_id, |project| {
+        // names of speakers found again are kept
+        project.speakers.retain(|label, _| labels.contains(&label.as_str()));
+        let mut n = 0;
+        for label in &labels {
+            if project.speakers.contains_key(*label) {
+                continue;
+            }
+            let name = loop {
+                n += 1;
+                let name = format!("Speaker {}", n);
+                if !project.speakers.values().any(|taken| *taken == name) {
+                    break name;
+                }
+            };
+            project.speakers.insert(label.to_string(), name);
+        }
+    })?;
+
+    let speakers = Speakers {
+        audio: fingerprint(audio)?,
+        turns,
+    };
+    let content = serde_json::to_string(&speakers)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize speakers: {}", e)))?;
+    fs::write(path(workspace, video_id)?, content)?;
+    label(workspace, video_id)?;
+    Ok(project.speakers)
+}
+
+fn audio(workspace: &Workspace, video_id: &str) -> Result<PathBuf> {
+    let audio = workspace.file(video_id, ".mp3")?;
+    if !audio.is_file() {
+        return Err(Error::NotFound(format!("No audio for project {}", video_id)));
+    }
+    Ok(audio)
+}
+
+fn path(workspace: &Workspace, video_id: &str) -> Result<PathBuf> {
+    workspace.file(video_id, "_speakers.json")
+}
+
+/// Hex SHA-256 of a file.
+fn fingerprint(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
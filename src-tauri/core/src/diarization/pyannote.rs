@@ -0,0 +1,365 @@
+//! Offline diarization with pyannote's segmentation model and a WeSpeaker
+//! embedding model, run with ONNX Runtime through `ort`.
+//!
+//! The audio is decoded to 16 kHz mono samples with ffmpeg and cut into
+//! 10 second windows. The segmentation model finds where someone speaks in
+//! every window, and a speech run ends at a pause or when another voice
+//! takes over. Each run gets a speaker embedding computed from its Kaldi
+//! filterbank features, and runs whose embeddings are close enough share a
+//! speaker, in order of appearance.
+
+use super::Turn;
+use crate::error::{Error, Result};
+use crate::ffmpeg::Progress;
+use crate::media;
+use crate::Observer;
+use ort::session::Session;
+use ort::value::TensorRef;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// Sample rate both models expect.
+const SAMPLE_RATE: u32 = 16_000;
+/// Samples the segmentation model looks at, at once.
+const WINDOW: usize = 10 * SAMPLE_RATE as usize;
+/// Cosine similarity above which a run is given an existing speaker.
+const DEFAULT_THRESHOLD: f32 = 0.5;
+/// Runs shorter than this, in seconds, are too short to tell a new speaker
+/// apart and get the closest known one.
+const MIN_NEW_SPEAKER_SECONDS: f64 = 1.0;
+
+// Kaldi filterbank settings the WeSpeaker models are trained with
+const FRAME_LENGTH: usize = 400;
+const FRAME_SHIFT: usize = 160;
+const FFT_SIZE: usize = 512;
+const MEL_BINS: usize = 80;
+const LOW_FREQUENCY: f32 = 20.0;
+const PREEMPHASIS: f32 = 0.97;
+
+pub struct PyannoteDiarizer {
+    segmentation_model: PathBuf,
+    embedding_model: PathBuf,
+    threshold: f32,
+}
+
+impl PyannoteDiarizer {
+    /// `segmentation_model` is pyannote's `segmentation-3.0` and
+    /// `embedding_model` a WeSpeaker model taking 80 filterbank bins, both
+    /// exported to ONNX.
+    pub fn new(segmentation_model: PathBuf, embedding_model: PathBuf, threshold: Option<f32>) -> Result<Self> {
+        for model in [&segmentation_model, &embedding_model] {
+            if !model.is_file() {
+                return Err(Error::InvalidInput(format!("Diarization model {:?} does not exist", model)));
+            }
+        }
+        let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+        if !(-1.0..=1.0).contains(&threshold) {
+            return Err(Error::InvalidInput(format!(
+                "Invalid speaker threshold {}, expected a cosine similarity from -1 to 1",
+                threshold
+            )));
+        }
+        Ok(PyannoteDiarizer {
+            segmentation_model,
+            embedding_model,
+            threshold,
+        })
+    }
+
+    /// Speaker turns of the mp3 `audio`. Cancelling the job drops this
+    /// future but not the blocking thread, whose result is then thrown away.
+    pub async fn run(&self, audio: &Path, observer: &dyn Observer) -> Result<Vec<Turn>> {
+        let samples = media::decode_samples(audio, SAMPLE_RATE).await?;
+        let duration = samples.len() as f64 / SAMPLE_RATE as f64;
+
+        let (progress_sender, mut progress_receiver) = mpsc::unbounded_channel();
+        let segmentation_model = self.segmentation_model.clone();
+        let embedding_model = self.embedding_model.clone();
+        let threshold = self.threshold;
+        let mut task = tokio::task::spawn_blocking(move || {
+            diarize_samples(&segmentation_model, &embedding_model, threshold, &samples, progress_sender)
+        });
+
+        loop {
+            tokio::select! {
+                Some(out_time) = progress_receiver.recv() => {
+                    let progress = Progress {
+                        out_time,
+                        duration: Some(duration),
+                        percent: Some(out_time / duration * 100.0),
+                        ..Default::default()
+                    };
+                    observer.progress("diarize", &progress);
+                }
+                result = &mut task => {
+                    return result
+                        .map_err(|e| Error::Diarization(format!("The diarizer crashed: {}", e)))?;
+                }
+            }
+        }
+    }
+}
+
+/// A run of speech of one voice, in samples.
+struct Run {
+    start: usize,
+    end: usize,
+}
+
+fn diarize_samples(
+    segmentation_model: &Path,
+    embedding_model: &Path,
+    threshold: f32,
+    samples: &[f32],
+    progress: mpsc::UnboundedSender<f64>,
+) -> Result<Vec<Turn>> {
+    let mut segmentation = load(segmentation_model)?;
+    let mut embedding = load(embedding_model)?;
+    let mut speakers = Speakers::new(threshold);
+    let mut turns = Vec::new();
+    let mut assign = |run: Run| -> Result<()> {
+        let Some(embedding) = embed(&mut embedding, &samples[run.start..run.end])? else {
+            return Ok(());
+        };
+        let seconds = (run.end - run.start) as f64 / SAMPLE_RATE as f64;
+        if let Some(speaker) = speakers.assign(&embedding, seconds >= MIN_NEW_SPEAKER_SECONDS) {
+            turns.push(Turn {
+                start: run.start as f64 / SAMPLE_RATE as f64,
+                end: run.end as f64 / SAMPLE_RATE as f64,
+                speaker: format!("SPEAKER_{:02}", speaker),
+            });
+        }
+        Ok(())
+    };
+
+    let mut current: Option<Run> = None;
+    for window_start in (0..samples.len()).step_by(WINDOW) {
+        let window_end = (window_start + WINDOW).min(samples.len());
+        let mut window = samples[window_start..window_end].to_vec();
+        window.resize(WINDOW, 0.0);
+        let input = TensorRef::from_array_view(([1usize, 1, WINDOW], window.as_slice()))
+            .map_err(|e| model_error("Failed to prepare the audio", e))?;
+        let outputs = segmentation
+            .run(ort::inputs![input])
+            .map_err(|e| model_error("Segmentation failed", e))?;
+        let (shape, scores) = outputs[0]
+            .try_extract_tensor::<f32>()
+            .map_err(|e| model_error("Unexpected segmentation output", e))?;
+        // [batch, frames, classes], the powerset classes being no speech, one
+        // of 3 local speakers or 2 of them at once
+        let (frames, classes) = match shape[..] {
+            [1, frames, classes] if frames > 0 && classes > 3 => (frames as usize, classes as usize),
+            _ => {
+                return Err(Error::Diarization(format!("Unexpected segmentation output shape {:?}", shape)));
+            }
+        };
+        let frame_step = WINDOW as f64 / frames as f64;
+
+        let mut voice = None;
+        for (frame, scores) in scores.chunks_exact(classes).enumerate() {
+            let position = window_start + ((frame as f64 + 0.5) * frame_step) as usize;
+            if position >= window_end {
+                break;
+            }
+            let class = scores
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map_or(0, |(class, _)| class);
+            let changed = match class {
+                0 => {
+                    if let Some(run) = current.take() {
+                        assign(run)?;
+                    }
+                    voice = None;
+                    continue;
+                }
+                1..=3 => voice.replace(class).is_some_and(|previous| previous != class),
+                // overlapping speech stays with the voice it started with
+                _ => false,
+            };
+            match current.as_mut() {
+                Some(run) if !changed => run.end = position,
+                _ => {
+                    if let Some(run) = current.replace(Run {
+                        start: position,
+                        end: position,
+                    }) {
+                        assign(run)?;
+                    }
+                }
+            }
+        }
+        // the receiver is gone once the job was cancelled
+        let _ = progress.send(window_end as f64 / SAMPLE_RATE as f64);
+    }
+    if let Some(run) = current.take() {
+        assign(run)?;
+    }
+    Ok(turns)
+}
+
+fn load(model: &Path) -> Result<Session> {
+    Session::builder()
+        .and_then(|builder| builder.commit_from_file(model))
+        .map_err(|e| model_error(&format!("Failed to load {:?}", model), e))
+}
+
+/// Speaker embedding of `samples`, `None` when they are too short for a
+/// single filterbank frame.
+fn embed(session: &mut Session, samples: &[f32]) -> Result<Option<Vec<f32>>> {
+    let features = fbank(samples);
+    let frames = features.len() / MEL_BINS;
+    if frames == 0 {
+        return Ok(None);
+    }
+    let input = TensorRef::from_array_view(([1usize, frames, MEL_BINS], features.as_slice()))
+        .map_err(|e| model_error("Failed to prepare the features", e))?;
+    let outputs = session
+        .run(ort::inputs![input])
+        .map_err(|e| model_error("Speaker embedding failed", e))?;
+    let (_, embedding) = outputs[0]
+        .try_extract_tensor::<f32>()
+        .map_err(|e| model_error("Unexpected embedding output", e))?;
+    Ok(Some(embedding.to_vec()))
+}
+
+/// Log mel filterbank energies of `samples`, [`MEL_BINS`] per 10 ms frame,
+/// computed like Kaldi's `compute-fbank-feats` with a Hamming window and
+/// without dithering, then mean normalized.
+fn fbank(samples: &[f32]) -> Vec<f32> {
+    if samples.len() < FRAME_LENGTH {
+        return Vec::new();
+    }
+    let frames = 1 + (samples.len() - FRAME_LENGTH) / FRAME_SHIFT;
+    let window: Vec<f32> = (0..FRAME_LENGTH)
+        .map(|i| 0.54 - 0.46 * (2.0 * PI * i as f32 / (FRAME_LENGTH - 1) as f32).cos())
+        .collect();
+    let banks = mel_banks();
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+
+    let mut features = Vec::with_capacity(frames * MEL_BINS);
+    let mut buffer = vec![Complex::new(0.0, 0.0); FFT_SIZE];
+    for frame in 0..frames {
+        // Kaldi works on 16-bit sample values
+        let mut x: Vec<f32> = samples[frame * FRAME_SHIFT..frame * FRAME_SHIFT + FRAME_LENGTH]
+            .iter()
+            .map(|sample| sample * 32768.0)
+            .collect();
+        let mean = x.iter().sum::<f32>() / FRAME_LENGTH as f32;
+        x.iter_mut().for_each(|sample| *sample -= mean);
+        for i in (1..FRAME_LENGTH).rev() {
+            x[i] -= PREEMPHASIS * x[i - 1];
+        }
+        x[0] -= PREEMPHASIS * x[0];
+
+        buffer.fill(Complex::new(0.0, 0.0));
+        for (i, sample) in x.iter().enumerate() {
+            buffer[i].re = sample * window[i];
+        }
+        fft.process(&mut buffer);
+        let power: Vec<f32> = buffer[..FFT_SIZE / 2].iter().map(|bin| bin.norm_sqr()).collect();
+        for bank in &banks {
+            let energy: f32 = bank.iter().map(|(bin, weight)| power[*bin] * weight).sum();
+            features.push(energy.max(f32::EPSILON).ln());
+        }
+    }
+
+    for bin in 0..MEL_BINS {
+        let mean = features.iter().skip(bin).step_by(MEL_BINS).sum::<f32>() / frames as f32;
+        features
+            .iter_mut()
+            .skip(bin)
+            .step_by(MEL_BINS)
+            .for_each(|feature| *feature -= mean);
+    }
+    features
+}
+
+/// Triangular mel filters from [`LOW_FREQUENCY`] to the Nyquist frequency,
+/// as `(fft bin, weight)` pairs.
+fn mel_banks() -> Vec<Vec<(usize, f32)>> {
+    let mel = |frequency: f32| 1127.0 * (1.0 + frequency / 700.0).ln();
+    let low = mel(LOW_FREQUENCY);
+    let high = mel(SAMPLE_RATE as f32 / 2.0);
+    let delta = (high - low) / (MEL_BINS + 1) as f32;
+    let bin_width = SAMPLE_RATE as f32 / FFT_SIZE as f32;
+    (0..MEL_BINS)
+        .map(|bank| {
+            let left = low + bank as f32 * delta;
+            let center = left + delta;
+            let right = center + delta;
+            (0..FFT_SIZE / 2)
+                .filter_map(|bin| {
+                    let m = mel(bin as f32 * bin_width);
+                    let weight = if m > left && m <= center {
+                        (m - left) / (center - left)
+                    } else if m > center && m < right {
+                        (right - m) / (right - center)
+                    } else {
+                        return None;
+                    };
+                    Some((bin, weight))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Speakers found so far, as the sum of their runs' normalized embeddings.
+struct Speakers {
+    centroids: Vec<Vec<f32>>,
+    threshold: f32,
+}
+
+impl Speakers {
+    fn new(threshold: f32) -> Self {
+        Speakers {
+            centroids: Vec::new(),
+            threshold,
+        }
+    }
+
+    /// Index of the speaker of `embedding`: the closest one when it is close
+    /// enough or a new one can't be added, otherwise a new one. `None`
+    /// when there is no speaker yet and no new one can be added.
+    fn assign(&mut self, embedding: &[f32], can_add: bool) -> Option<usize> {
+        let embedding = normalize(embedding);
+        let closest = self
+            .centroids
+            .iter()
+            .map(|centroid| cosine(centroid, &embedding))
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        let speaker = match closest {
+            Some((speaker, similarity)) if similarity >= self.threshold || !can_add => speaker,
+            _ if can_add => {
+                self.centroids.push(vec![0.0; embedding.len()]);
+                self.centroids.len() - 1
+            }
+            _ => return None,
+        };
+        for (sum, value) in self.centroids[speaker].iter_mut().zip(&embedding) {
+            *sum += value;
+        }
+        Some(speaker)
+    }
+}
+
+fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    vector.iter().map(|value| value / norm.max(f32::EPSILON)).collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |vector: &[f32]| vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    dot / (norm(a) * norm(b)).max(f32::EPSILON)
+}
+
+fn model_error(context: &str, e: ort::Error) -> Error {
+    Error::Diarization(format!("{}: {}", context, e))
+}
@@ -0,0 +1,95 @@
+//! Downloading videos from YouTube.
+
+use crate::error::{Error, Result};
+use crate::media;
+use crate::storage::{Project, Source, Workspace};
+use crate::toolchain::{self, Feature};
+use crate::Observer;
+use rusty_ytdl::{Video, VideoOptions, VideoQuality, VideoSearchOptions};
+
+/// What [`download`] needs from ffmpeg, to convert the audio track to mp3.
+pub const DOWNLOAD_FEATURES: &[Feature] = &[Feature::Libmp3lame];
+
+/// Downloads a YouTube video into its project folder and returns the video id.
+/// Does nothing but update the manifest if it was downloaded before.
+pub async fn download(workspace: &Workspace, url: &str, observer: &dyn Observer) -> Result<String> {
+    toolchain::require(DOWNLOAD_FEATURES).await?;
+
+    let video_options = VideoOptions {
+        quality: VideoQuality::HighestVideo,
+        filter: VideoSearchOptions::Video,
+        ..Default::default()
+    };
+
+    let audio_options = VideoOptions {
+        quality: VideoQuality::HighestAudio,
+        filter: VideoSearchOptions::Audio,
+        ..Default::default()
+    };
+
+    let video = Video::new_with_options(url, video_options)
+        .map_err(|e| Error::InvalidInput(format!("Invalid YouTube URL: {}", e)))?;
+    let video_info = video
+        .get_info()
+        .await
+        .map_err(|e| Error::Download(format!("Failed to get video info: {}", e)))?;
+    let video_id = video_info.video_details.video_id;
+    let title = video_info.video_details.title;
+    let channel = Some(video_info.video_details.channel_id).filter(|id| !id.is_empty());
+    let record_source = || {
+        Project::update(workspace, &video_id, |project| {
+            project.source = Source::Youtube {
+                url: url.to_string(),
+            };
+            project.title = Some(title.clone());
+            project.channel = channel.clone();
+        })
+    };
+    let video_path = workspace.file(&video_id, ".mp4")?;
+
+    if video_path.exists() {
+        record_source()?;
+        return Ok(video_id);
+    }
+
+    let audio = Video::new_with_options(url, audio_options)
+        .map_err(|e| Error::InvalidInput(format!("Invalid YouTube URL: {}", e)))?;
+
+    let audio_extension = audio
+        .get_info()
+        .await
+        .map_err(|e| Error::Download(format!("Failed to get audio info: {}", e)))?
+        .formats
+        .iter()
+        .filter(|f| f.mime_type.mime.to_string().contains("audio"))
+        .max_by_key(|f| f.bitrate)
+        .map(|f| f.mime_type.container.to_string())
+        .ok_or_else(|| Error::Download("No audio format available".to_string()))?;
+
+    let video_path = workspace.file(&video_id, "_noaudio.mp4")?;
+    let audio_path = workspace.file(&video_id, &format!("_audio_track.{}", audio_extension))?;
+
+    if video_path.exists() {
+        record_source()?;
+        return Ok(video_id);
+    }
+
+    observer.output(&video_path);
+    observer.output(&audio_path);
+    observer.output(&workspace.file(&video_id, ".mp4")?);
+    observer.output(&workspace.file(&video_id, "_full.mp3")?);
+
+    video
+        .download(video_path)
+        .await
+        .map_err(|e| Error::Download(format!("Failed to download video: {}", e)))?;
+    audio
+        .download(audio_path)
+        .await
+        .map_err(|e| Error::Download(format!("Failed to download audio: {}", e)))?;
+
+    media::merge_audio(workspace, &video_id, &audio_extension, observer).await?;
+    record_source()?;
+
+    Ok(video_id)
+}
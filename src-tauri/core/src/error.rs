@@ -0,0 +1,169 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+/// Error returned by every command.
+///
+/// It reaches the webview as `{ "kind": "ffmpeg", "message": "..." }`, where
+/// `kind` is one of the stable codes returned by [`Error::kind`]. Failed
+/// processes and transcription requests also carry a `details` object, see
+/// [`ProcessFailure`] and [`ApiFailure`].
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Fetching the video or its metadata from YouTube failed.
+    Download(String),
+    /// ffmpeg/ffprobe could not be started or did not produce its output.
+    Ffmpeg(String),
+    /// A process ran but exited with a failure status, ffmpeg/ffprobe unless
+    /// the failure says otherwise.
+    ProcessFailed(Box<ProcessFailure>),
+    /// ffmpeg/ffprobe is missing, too old or lacks a feature the step needs.
+    Environment(String),
+    /// The transcription API rejected the request or returned garbage.
+    Transcription(String),
+    /// A transcription or translation request failed for good, after any
+    /// retries.
+    ApiFailed(Box<ApiFailure>),
+    /// The diarizer failed or did not find speaker turns.
+    Diarization(String),
+    /// The translation endpoint or chat model returned garbage.
+    Translation(String),
+    /// Reading or writing a file on disk failed.
+    Io(String),
+    /// The arguments sent by the frontend are not usable.
+    InvalidInput(String),
+    /// A file the command depends on does not exist yet.
+    NotFound(String),
+    /// The job was cancelled before it finished.
+    Cancelled(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What we know about a failed process run, enough for a bug report.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessFailure {
+    #[serde(skip)]
+    pub message: String,
+    /// The [`Error::kind`] reported, `ffmpeg` for ffmpeg/ffprobe and the
+    /// kind of the step for the programs run by [`crate::runner::run_program`].
+    #[serde(skip)]
+    pub kind: &'static str,
+    /// The command line as it could be pasted in a shell.
+    pub command: String,
+    /// `None` when the process was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// Last lines the process wrote to stderr, where it reports what went
+    /// wrong.
+    pub stderr_tail: Vec<String>,
+}
+
+/// Why an API request failed, so the UI can tell the user what to fix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ApiFailureReason {
+    /// The API key is missing, wrong or not allowed to use the model.
+    Auth,
+    /// The account ran out of credit.
+    Quota,
+    /// Too many requests, still failing after waiting.
+    RateLimited,
+    /// The audio is too large, corrupt or in a format the API refuses.
+    InvalidAudio,
+    /// Any other 4xx answer.
+    Rejected,
+    /// The server kept failing with 5xx answers.
+    Server,
+    /// No answer within the configured timeout.
+    Timeout,
+    /// The server could not be reached.
+    Network,
+    /// The answer was not a transcription.
+    BadResponse,
+}
+
+/// What we know about a failed transcription or translation request.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiFailure {
+    #[serde(skip)]
+    pub message: String,
+    /// The [`Error::kind`] reported, `transcription` or `translation`.
+    #[serde(skip)]
+    pub kind: &'static str,
+    pub reason: ApiFailureReason,
+    /// HTTP status, `None` when no answer came back.
+    pub status: Option<u16>,
+    /// Start of the response body, usually the API's error object.
+    pub body: Option<String>,
+    /// Requests made, retries included.
+    pub attempts: u32,
+}
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Download(_) => "download",
+            Error::Ffmpeg(_) => "ffmpeg",
+            Error::ProcessFailed(failure) => failure.kind,
+            Error::Environment(_) => "environment",
+            Error::Transcription(_) => "transcription",
+            Error::ApiFailed(failure) => failure.kind,
+            Error::Diarization(_) => "diarization",
+            Error::Translation(_) => "translation",
+            Error::Io(_) => "io",
+            Error::InvalidInput(_) => "invalid_input",
+            Error::NotFound(_) => "not_found",
+            Error::Cancelled(_) => "cancelled",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Download(message)
+            | Error::Ffmpeg(message)
+            | Error::Environment(message)
+            | Error::Transcription(message)
+            | Error::Diarization(message)
+            | Error::Translation(message)
+            | Error::Io(message)
+            | Error::InvalidInput(message)
+            | Error::NotFound(message)
+            | Error::Cancelled(message) => message,
+            Error::ProcessFailed(failure) => &failure.message,
+            Error::ApiFailed(failure) => &failure.message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind(), self.message())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            Error::NotFound(err.to_string())
+        } else {
+            Error::Io(err.to_string())
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", self.message())?;
+        match self {
+            Error::ProcessFailed(failure) => state.serialize_field("details", failure)?,
+            Error::ApiFailed(failure) => state.serialize_field("details", failure)?,
+            _ => state.skip_field("details")?,
+        }
+        state.end()
+    }
+}
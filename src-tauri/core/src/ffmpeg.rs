@@ -0,0 +1,87 @@
+//! Runs ffmpeg with `-progress pipe:1` and turns its `key=value` blocks into
+//! [`Progress`] updates.
+
+use crate::error::Result;
+use crate::runner;
+use crate::toolchain;
+use serde::Serialize;
+use std::process::{Command, Output};
+
+/// Snapshot of a running ffmpeg render, sent once per progress block.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    /// Seconds of output written so far.
+    pub out_time: f64,
+    /// Expected output duration in seconds, when known.
+    pub duration: Option<f64>,
+    /// `out_time / duration`, between 0 and 100.
+    pub percent: Option<f64>,
+    /// Encoding speed relative to realtime (`1.5` means 1.5x).
+    pub speed: Option<f64>,
+    pub fps: Option<f64>,
+    /// Estimated seconds left, from the remaining duration and the speed.
+    pub eta: Option<f64>,
+    /// Set on the final block ffmpeg writes before exiting.
+    pub done: bool,
+}
+
+/// An `ffmpeg` command that reports progress on stdout. Add the usual
+/// arguments to it and pass it to [`run_with_progress`].
+pub fn progress_command() -> Command {
+    let mut command = toolchain::ffmpeg();
+    command.args(["-progress", "pipe:1", "-nostats"]);
+    command
+}
+
+/// Runs a command built with [`progress_command`], calling `on_progress`
+/// for every block ffmpeg reports. `duration` is the expected length of the
+/// output and is used to compute the percentage and ETA.
+///
+/// The returned `stdout` is empty since ffmpeg's stdout carries the progress.
+pub async fn run_with_progress(
+    command: Command,
+    duration: Option<f64>,
+    mut on_progress: impl FnMut(&Progress) + Send,
+) -> Result<Output> {
+    let mut progress = Progress {
+        duration,
+        ..Default::default()
+    };
+
+    runner::run_streaming(command, None, |line| {
+        let Some((key, value)) = line.split_once('=') else {
+            return;
+        };
+        let value = value.trim();
+
+        match key {
+            // out_time_ms is in microseconds as well, kept for older ffmpeg builds
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    progress.out_time = us.max(0) as f64 / 1_000_000.0;
+                }
+            }
+            "speed" => progress.speed = value.trim_end_matches('x').parse().ok(),
+            "fps" => progress.fps = value.parse().ok(),
+            "progress" => {
+                progress.done = value == "end";
+                if let Some(duration) = duration.filter(|d| *d > 0.0) {
+                    let out_time = if progress.done {
+                        duration
+                    } else {
+                        progress.out_time.min(duration)
+                    };
+                    progress.percent = Some(out_time / duration * 100.0);
+                    progress.eta = progress
+                        .speed
+                        .filter(|speed| *speed > 0.0)
+                        .map(|speed| (duration - out_time) / speed);
+                }
+                on_progress(&progress);
+            }
+            _ => {}
+        }
+    })
+    .await
+}
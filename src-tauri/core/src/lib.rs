@@ -0,0 +1,67 @@
+//! The clip pipeline behind the ytb-editor app and `ytb-editor-cli`:
+//! download or import a video, trim it, transcribe it into subtitle variants
+//! and burn one of them in.
+//!
+//! - [`download`] fetches YouTube videos.
+//! - [`media`] imports, trims and renders with ffmpeg.
+//! - [`probe`] reads a media file's container and streams with ffprobe.
+//! - [`transcription`] turns a project's audio into a transcription with a
+//!   pluggable backend.
+//! - [`subtitles`] generates and converts the WebVTT subtitle variants.
+//! - [`corrections`] learns from subtitle edits and fixes later transcripts.
+//! - [`diarization`] labels the cues with who is speaking.
+//! - [`translation`] makes subtitle tracks in other languages.
+//! - [`storage`] is where every project's files and manifest live.
+//! - [`toolchain`] finds and checks the ffmpeg and ffprobe binaries.
+//!
+//! Every step works on a project folder of a [`storage::Workspace`] and
+//! records what it did in the project manifest. Steps that run ffmpeg report
+//! their progress and outputs to an [`Observer`]. Diagnostics go to stderr,
+//! stdout is left to the caller.
+//!
+//! ```no_run
+//! use ytb_editor_core::storage::{Timing, Workspace};
+//! use ytb_editor_core::transcription::{self, BackendConfig, Options};
+//! use ytb_editor_core::{download, media};
+//!
+//! # async fn clip() -> ytb_editor_core::error::Result<()> {
+//! let workspace = Workspace::new("workspace")?;
+//! let id = download::download(&workspace, "https://www.youtube.com/watch?v=wLJLLKMdxnQ", &()).await?;
+//! let timings = vec![Timing { start: 10.0, end: 40.0 }];
+//! media::trim(&workspace, &id, timings, None, &()).await?;
+//! let backend = BackendConfig::default().build(Some("sk-..."))?;
+//! transcription::transcribe(&workspace, &id, backend.as_ref(), &Options::new("en"), &()).await?;
+//! media::burn(&workspace, &id, "3words", 1920, &()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod corrections;
+pub mod diarization;
+pub mod download;
+pub mod error;
+pub mod ffmpeg;
+pub mod media;
+pub mod probe;
+pub mod runner;
+pub mod storage;
+pub mod subtitles;
+pub mod toolchain;
+pub mod transcription;
+pub mod translation;
+
+use ffmpeg::Progress;
+use std::path::Path;
+
+/// Receives what a pipeline step reports while it runs.
+pub trait Observer: Send + Sync {
+    /// ffmpeg progress of `stage`, e.g. "trim" or "burn".
+    fn progress(&self, _stage: &str, _progress: &Progress) {}
+
+    /// A file or folder the step is about to write, to be deleted if the
+    /// step is abandoned halfway.
+    fn output(&self, _path: &Path) {}
+}
+
+/// Ignores everything.
+impl Observer for () {}
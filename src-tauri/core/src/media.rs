@@ -0,0 +1,392 @@
+//! Video processing with ffmpeg: importing, trimming and burning subtitles
+//! in. Steps that run ffmpeg report to an [`Observer`].
+
+use crate::error::{Error, Result};
+use crate::ffmpeg;
+use crate::probe;
+use crate::runner;
+use crate::storage::{Dimensions, Project, Source, Timing, Workspace};
+use crate::subtitles;
+use crate::toolchain::{self, Feature};
+use crate::Observer;
+use std::{fs, path::Path};
+use tokio::fs as tokio_fs;
+use uuid::Uuid;
+
+/// What [`trim`] needs from ffmpeg.
+pub const TRIM_FEATURES: &[Feature] = &[Feature::Libx264, Feature::Libmp3lame];
+/// What [`burn`] needs from ffmpeg.
+pub const BURN_FEATURES: &[Feature] = &[Feature::Libass, Feature::Libx264];
+/// What [`import`] needs from ffmpeg.
+pub const IMPORT_FEATURES: &[Feature] = &[Feature::Libx264, Feature::Libmp3lame];
+
+/// Fails with [`Error::InvalidInput`] unless there is at least one timing and
+/// every timing ends after it starts.
+pub fn validate_timings(timings: &[Timing]) -> Result<()> {
+    if timings.is_empty() {
+        return Err(Error::InvalidInput("No timings to trim".to_string()));
+    }
+    if let Some(timing) = timings.iter().find(|t| t.end <= t.start) {
+        return Err(Error::InvalidInput(format!(
+            "Timing ends before it starts: {} -> {}",
+            timing.start, timing.end
+        )));
+    }
+    Ok(())
+}
+
+/// Extracts the audio of `{id}_trimmed.mp4` to `{id}.{audio_format}`.
+pub async fn extract_audio(
+    workspace: &Workspace,
+    video_id: String,
+    audio_format: String,
+) -> Result<()> {
+    let input = workspace.file(&video_id, "_trimmed.mp4")?;
+    let output = workspace.file(&video_id, &format!(".{}", audio_format))?;
+
+    let mut command = toolchain::ffmpeg();
+    command
+        .args(["-y", "-i"])
+        .arg(&input)
+        .args([
+            "-vn",
+            "-acodec",
+            if audio_format == "mp3" {
+                "libmp3lame"
+            } else {
+                "aac"
+            },
+        ])
+        .arg(&output);
+    runner::run(command, None).await?;
+
+    Ok(())
+}
+
+/// Decodes `audio` to 32-bit float mono samples at `sample_rate`, for the
+/// models run in-process.
+#[cfg(any(feature = "whisper", feature = "diarization"))]
+pub async fn decode_samples(audio: &Path, sample_rate: u32) -> Result<Vec<f32>> {
+    let mut command = toolchain::ffmpeg();
+    command
+        .args(["-v", "error", "-i"])
+        .arg(audio)
+        .args(["-ar", &sample_rate.to_string(), "-ac", "1", "-f", "f32le", "-"]);
+    let output = runner::run(command, None).await?;
+
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
+}
+
+/// Container duration in seconds, `None` if it can't be probed. Only used
+/// to turn ffmpeg progress into a fraction.
+async fn duration(media_path: &Path) -> Option<f64> {
+    probe::probe(media_path).await.ok().and_then(|info| info.duration)
+}
+
+/// Cuts `timings` out of `{id}.mp4`, optionally cropped, into
+/// `{id}_trimmed.mp4` and extracts its audio. Returns the output file name.
+pub async fn trim(
+    workspace: &Workspace,
+    video_id: &str,
+    timings: Vec<Timing>,
+    dimensions: Option<Dimensions>,
+    observer: &dyn Observer,
+) -> Result<String> {
+    validate_timings(&timings)?;
+    toolchain::require(TRIM_FEATURES).await?;
+    observer.output(&workspace.file(video_id, "_trimmed.mp4")?);
+    observer.output(&workspace.file(video_id, ".mp3")?);
+
+    let input = workspace.file(video_id, ".mp4")?;
+    let output = workspace.file(video_id, "_trimmed.mp4")?;
+
+    let mut filters = Vec::new();
+    let mut filter_complex = String::new();
+
+    let info = probe::probe(&input).await?;
+    let (video_width, video_height) = info
+        .require_video(&input)?
+        .frame_size()
+        .ok_or_else(|| Error::Ffmpeg(format!("Failed to get video dimensions of {:?}", input)))?;
+    if info.audio().is_none() {
+        return Err(Error::InvalidInput(format!(
+            "{:?} has no audio track",
+            input
+        )));
+    }
+    if let Some(duration) = info.duration {
+        if let Some(timing) = timings.iter().find(|t| t.start as f64 >= duration) {
+            return Err(Error::InvalidInput(format!(
+                "Timing starts at {} but the video is only {:.2}s long",
+                timing.start, duration
+            )));
+        }
+    }
+
+    for (i, timing) in timings.iter().enumerate() {
+        let mut filter = format!(
+            "[0:v]trim=start={}:end={},setpts=PTS-STARTPTS[v{}];[0:a]atrim=start={}:end={},asetpts=PTS-STARTPTS[a{}];",
+            timing.start,
+            timing.end,
+            i,
+            timing.start,
+            timing.end,
+            i
+        );
+
+        if let Some(dimensions) = &dimensions {
+            let width = (video_width as f32 * dimensions.width / 100.0) as i32;
+            let height = (video_height as f32 * dimensions.height / 100.0) as i32;
+            let x = (video_width as f32 * dimensions.x / 100.0) as i32;
+            let y = (video_height as f32 * dimensions.y / 100.0) as i32;
+
+            filter += &format!(
+                "[v{}]crop={}:{}:{}:{},scale=-1:1920,setpts=PTS-STARTPTS[v{}];",
+                i, width, height, x, y, i
+            );
+        }
+
+        filters.push(format!("[v{}][a{}]", i, i));
+        filter_complex.push_str(&filter);
+    }
+
+    filter_complex.push_str(&format!(
+        "{}concat=n={}:v=1:a=1[outv][outa]",
+        filters.join(""),
+        timings.len()
+    ));
+
+    let duration: f64 = timings.iter().map(|t| (t.end - t.start) as f64).sum();
+
+    let mut command = ffmpeg::progress_command();
+    command
+        .args(["-y", "-i"])
+        .arg(&input)
+        .args([
+            "-filter_complex",
+            &filter_complex,
+            "-map",
+            "[outv]",
+            "-map",
+            "[outa]",
+        ])
+        .arg(&output);
+    ffmpeg::run_with_progress(
+        command,
+        Some(duration),
+        |progress| observer.progress("trim", progress),
+    )
+    .await?;
+
+    // extract audio
+    let audio_format = "mp3";
+    extract_audio(workspace, video_id.to_string(), audio_format.to_string()).await?;
+
+    Project::update(workspace, video_id, |project| {
+        project.timings = timings;
+        project.crop = dimensions;
+    })?;
+
+    Ok(format!("{}_trimmed.mp4", video_id))
+}
+
+/// Muxes the separately downloaded video and audio tracks into `{id}.mp4`
+/// and converts the audio to `{id}_full.mp3`.
+pub(crate) async fn merge_audio(
+    workspace: &Workspace,
+    video_id: &str,
+    audio_format: &str,
+    observer: &dyn Observer,
+) -> Result<()> {
+    let video_input = workspace.file(video_id, "_noaudio.mp4")?;
+    let audio_input = workspace.file(video_id, &format!("_audio_track.{}", audio_format))?;
+    let output = workspace.file(video_id, ".mp4")?;
+    let mp3_output = workspace.file(video_id, "_full.mp3")?;
+
+    let mut command = ffmpeg::progress_command();
+    command
+        .args(["-y", "-i"])
+        .arg(&video_input)
+        .arg("-i")
+        .arg(&audio_input)
+        .args([
+            "-c",
+            "copy",
+            "-map",
+            "0:v", // Map video stream from the MP4 file
+            "-map",
+            "1:a", // Map audio stream from the WebM file
+        ])
+        .arg(&output);
+    ffmpeg::run_with_progress(
+        command,
+        duration(&video_input).await,
+        |progress| observer.progress("merge", progress),
+    )
+    .await?;
+
+    // Convert audio to mp3 if it's not already in mp3 format
+    if audio_format != "mp3" {
+        let mut command = ffmpeg::progress_command();
+        command
+            .args(["-y", "-i"])
+            .arg(&audio_input)
+            .args(["-vn", "-ar", "44100", "-ac", "2", "-b:a", "192k"])
+            .arg(&mp3_output);
+        ffmpeg::run_with_progress(
+            command,
+            duration(&audio_input).await,
+            |progress| observer.progress("convert_audio", progress),
+        )
+        .await?;
+
+        // Delete the original audio file
+        fs::remove_file(&audio_input)?;
+    }
+    fs::remove_file(&video_input)?;
+
+    Ok(())
+}
+
+/// Burns a subtitle variant into `{id}_trimmed.mp4`, writing `{id}_burned.mp4`.
+/// `video_height` is the height the cue `line:` positions were chosen for.
+/// Returns the output file name.
+pub async fn burn(
+    workspace: &Workspace,
+    video_id: &str,
+    sub_type: &str,
+    video_height: i32,
+    observer: &dyn Observer,
+) -> Result<String> {
+    subtitles::check_subtitle_variant(workspace, video_id, sub_type)?;
+    toolchain::require(BURN_FEATURES).await?;
+    observer.output(&workspace.file(video_id, "_burned.mp4")?);
+
+    // convert vtt to ass subtitle
+    subtitles::vtt_to_ass(workspace, video_id, sub_type, video_height)?;
+
+    let input = workspace.file(video_id, "_trimmed.mp4")?;
+    let output = workspace.file(video_id, "_burned.mp4")?;
+
+    // Run from the project folder so the subtitles filter gets a relative
+    // path and we don't have to escape drive letters and separators.
+    let mut command = ffmpeg::progress_command();
+    command
+        .current_dir(workspace.project_dir(video_id)?)
+        .args(["-y", "-i"])
+        .arg(&input)
+        .args(["-vf", &format!("subtitles={}.ass", video_id)])
+        .arg(&output);
+    ffmpeg::run_with_progress(
+        command,
+        duration(&input).await,
+        |progress| observer.progress("burn", progress),
+    )
+    .await?;
+
+    let output = format!("{}_burned.mp4", video_id);
+    Project::update(workspace, video_id, |project| {
+        project.subtitle_variant = Some(sub_type.to_string());
+        project.add_render(&output, sub_type, video_height);
+    })?;
+
+    Ok(output)
+}
+
+/// Fails with [`Error::InvalidInput`] unless the file is an mp4, webm or mkv video.
+pub fn check_import_format(file_path: &str) -> Result<()> {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str);
+    if !matches!(extension, Some("mp4" | "webm" | "mkv")) {
+        return Err(unsupported_file_format(file_path));
+    }
+    Ok(())
+}
+
+/// Copies (or converts to mp4) a local video into a new project and returns its id.
+/// The project folder is removed when the import fails.
+pub async fn import(workspace: &Workspace, file_path: &str, observer: &dyn Observer) -> Result<String> {
+    check_import_format(file_path)?;
+    toolchain::require(IMPORT_FEATURES).await?;
+    let random_id: String = Uuid::new_v4().to_string();
+
+    let result = import_into(workspace, &random_id, file_path, observer).await;
+    if result.is_err() {
+        if let Err(e) = workspace.remove_project(&random_id) {
+            eprintln!("Error removing project {}: {}", random_id, e);
+        }
+    }
+    result.map(|()| random_id)
+}
+
+async fn import_into(
+    workspace: &Workspace,
+    random_id: &str,
+    file_path: &str,
+    observer: &dyn Observer,
+) -> Result<()> {
+    let path = Path::new(file_path);
+    let extension = path.extension().and_then(std::ffi::OsStr::to_str);
+
+    probe::probe(path).await?.require_video(path)?;
+
+    observer.output(&workspace.project_dir(random_id)?);
+    let output = workspace.file(random_id, ".mp4")?;
+
+    let mp4_filename = match extension {
+        Some("mp4") => {
+            // copy the file to the project folder with the random id as the filename
+            tokio_fs::copy(path, &output).await?;
+            output
+        },
+        Some("webm") | Some("mkv") => {
+            // convert the file to mp4 and use the random id as the filename
+            let mut command = ffmpeg::progress_command();
+            command.args(["-i", file_path]).arg(&output);
+            ffmpeg::run_with_progress(
+                command,
+                duration(path).await,
+                |progress| observer.progress("convert", progress),
+            )
+            .await?;
+            output
+        }
+        _ => return Err(unsupported_file_format(file_path)),
+    };
+    eprintln!("Processed file: {:?}", mp4_filename);
+
+    let audio_output = workspace.file(random_id, "_full.mp3")?;
+
+    let mut command = ffmpeg::progress_command();
+    command
+        .arg("-i")
+        .arg(&mp4_filename)
+        .args(["-vn", "-acodec", "libmp3lame"])
+        .arg(&audio_output);
+    ffmpeg::run_with_progress(
+        command,
+        duration(&mp4_filename).await,
+        |progress| observer.progress("extract_audio", progress),
+    )
+    .await?;
+
+    let mut project = Project::new(
+        random_id,
+        Source::Upload {
+            path: file_path.to_string(),
+        },
+    );
+    project.title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned());
+    project.save(workspace)
+}
+
+fn unsupported_file_format(file_path: &str) -> Error {
+    Error::InvalidInput(format!("Unsupported file format: {}", file_path))
+}
@@ -0,0 +1,255 @@
+//! What a media file contains, read from `ffprobe -print_format json`.
+
+use crate::error::{Error, Result};
+use crate::runner;
+use crate::toolchain;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Relative gap between the nominal and the average frame rate above which a
+/// stream is reported as variable frame rate.
+const VFR_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfo {
+    /// Seconds, from the container.
+    pub duration: Option<f64>,
+    /// ffprobe format name, e.g. "mov,mp4,m4a,3gp,3g2,mj2" or "matroska,webm".
+    pub container: Option<String>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+    /// Overall bit rate in bits per second.
+    pub bit_rate: Option<u64>,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamInfo {
+    pub index: u32,
+    pub kind: StreamKind,
+    /// Short codec name, e.g. "h264" or "aac".
+    pub codec: Option<String>,
+    pub bit_rate: Option<u64>,
+    pub duration: Option<f64>,
+    pub language: Option<String>,
+    /// Cover art and other still images attached to the file.
+    pub attached_picture: bool,
+
+    /// Coded width, before rotation.
+    pub width: Option<u32>,
+    /// Coded height, before rotation.
+    pub height: Option<u32>,
+    pub pixel_format: Option<String>,
+    /// Average frames per second.
+    pub fps: Option<f64>,
+    /// The nominal and average frame rates disagree, which is how ffprobe
+    /// exposes variable frame rate without decoding every frame.
+    pub variable_frame_rate: bool,
+    /// Clockwise rotation players apply, one of 0, 90, 180 or 270.
+    pub rotation: u32,
+    /// Sample aspect ratio, e.g. "1:1".
+    pub sample_aspect_ratio: Option<String>,
+    /// Display aspect ratio, e.g. "16:9".
+    pub display_aspect_ratio: Option<String>,
+
+    /// Samples per second.
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+}
+
+impl MediaInfo {
+    /// The main video stream, skipping cover art.
+    pub fn video(&self) -> Option<&StreamInfo> {
+        self.streams
+            .iter()
+            .find(|stream| stream.kind == StreamKind::Video && !stream.attached_picture)
+    }
+
+    pub fn audio(&self) -> Option<&StreamInfo> {
+        self.streams
+            .iter()
+            .find(|stream| stream.kind == StreamKind::Audio)
+    }
+
+    /// The main video stream, or [`Error::InvalidInput`] if there is none.
+    pub fn require_video(&self, path: &Path) -> Result<&StreamInfo> {
+        self.video()
+            .ok_or_else(|| Error::InvalidInput(format!("{:?} has no video stream", path)))
+    }
+}
+
+impl StreamInfo {
+    /// Width and height of the frames ffmpeg filters see, i.e. after the
+    /// rotation ffmpeg applies on decode.
+    pub fn frame_size(&self) -> Option<(u32, u32)> {
+        let (width, height) = (self.width?, self.height?);
+        if self.rotation % 180 == 90 {
+            Some((height, width))
+        } else {
+            Some((width, height))
+        }
+    }
+}
+
+/// Probes a media file's container and streams.
+pub async fn probe(path: &Path) -> Result<MediaInfo> {
+    let mut command = toolchain::ffprobe();
+    command
+        .args([
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        .arg(path);
+    let output = runner::run(command, Some(runner::PROBE_TIMEOUT)).await?;
+
+    let raw: RawProbe = serde_json::from_slice(&output.stdout).map_err(|e| {
+        Error::Ffmpeg(format!("Failed to parse ffprobe output for {:?}: {}", path, e))
+    })?;
+    Ok(raw.into_info())
+}
+
+#[derive(Deserialize)]
+struct RawProbe {
+    #[serde(default)]
+    streams: Vec<RawStream>,
+    format: Option<RawFormat>,
+}
+
+#[derive(Deserialize)]
+struct RawFormat {
+    format_name: Option<String>,
+    duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawStream {
+    index: u32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    pix_fmt: Option<String>,
+    sample_aspect_ratio: Option<String>,
+    display_aspect_ratio: Option<String>,
+    r_frame_rate: Option<String>,
+    avg_frame_rate: Option<String>,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    #[serde(default)]
+    disposition: HashMap<String, i64>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<Value>,
+}
+
+impl RawProbe {
+    fn into_info(self) -> MediaInfo {
+        let format = self.format;
+        MediaInfo {
+            duration: format.as_ref().and_then(|f| parse(&f.duration)),
+            container: format.as_ref().and_then(|f| f.format_name.clone()),
+            size: format.as_ref().and_then(|f| parse(&f.size)),
+            bit_rate: format.as_ref().and_then(|f| parse(&f.bit_rate)),
+            streams: self.streams.into_iter().map(RawStream::into_info).collect(),
+        }
+    }
+}
+
+impl RawStream {
+    fn into_info(self) -> StreamInfo {
+        let kind = match self.codec_type.as_deref() {
+            Some("video") => StreamKind::Video,
+            Some("audio") => StreamKind::Audio,
+            Some("subtitle") => StreamKind::Subtitle,
+            Some("data") => StreamKind::Data,
+            Some("attachment") => StreamKind::Attachment,
+            _ => StreamKind::Other,
+        };
+        let nominal_fps = self.r_frame_rate.as_deref().and_then(parse_rational);
+        let fps = self.avg_frame_rate.as_deref().and_then(parse_rational).or(nominal_fps);
+        let variable_frame_rate = match (nominal_fps, fps) {
+            (Some(nominal), Some(average)) => (nominal - average).abs() / nominal > VFR_TOLERANCE,
+            _ => false,
+        };
+
+        StreamInfo {
+            index: self.index,
+            kind,
+            codec: self.codec_name,
+            bit_rate: parse(&self.bit_rate),
+            duration: parse(&self.duration),
+            language: self.tags.get("language").cloned(),
+            attached_picture: self.disposition.get("attached_pic").is_some_and(|v| *v != 0),
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pix_fmt,
+            fps,
+            variable_frame_rate,
+            rotation: rotation(&self.side_data_list, &self.tags),
+            sample_aspect_ratio: self.sample_aspect_ratio,
+            display_aspect_ratio: self.display_aspect_ratio,
+            sample_rate: parse(&self.sample_rate),
+            channels: self.channels,
+            channel_layout: self.channel_layout,
+        }
+    }
+}
+
+/// Clockwise rotation from the display matrix side data (counter-clockwise
+/// degrees), or the legacy `rotate` tag (clockwise degrees).
+fn rotation(side_data: &[Value], tags: &HashMap<String, String>) -> u32 {
+    let from_matrix = side_data
+        .iter()
+        .find(|data| data.get("side_data_type").and_then(Value::as_str) == Some("Display Matrix"))
+        .and_then(|data| data.get("rotation"))
+        .and_then(Value::as_f64)
+        .map(|degrees| -degrees);
+    let from_tag = || tags.get("rotate").and_then(|rotate| rotate.parse::<f64>().ok());
+
+    match from_matrix.or_else(from_tag) {
+        // snap to quarter turns, other angles are not something players apply
+        Some(degrees) => ((degrees / 90.0).round() as i64).rem_euclid(4) as u32 * 90,
+        None => 0,
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &Option<String>) -> Option<T> {
+    value.as_deref().and_then(|value| value.parse().ok())
+}
+
+/// "30000/1001" -> 29.97. ffprobe writes "0/0" when it does not know.
+fn parse_rational(value: &str) -> Option<f64> {
+    let (num, den) = value.split_once('/')?;
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    if num > 0.0 && den > 0.0 {
+        Some(num / den)
+    } else {
+        None
+    }
+}
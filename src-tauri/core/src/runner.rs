@@ -0,0 +1,168 @@
+//! Async execution of ffmpeg/ffprobe on `tokio::process`, and of the
+//! external transcription and diarization programs with [`run_program`].
+//!
+//! Children are killed when the returned future is dropped (job cancelled) or
+//! when the timeout expires, and stdout/stderr are drained concurrently so a
+//! chatty process never blocks on a full pipe. A non-zero exit status is
+//! turned into [`Error::ProcessFailed`] with the tail of stderr.
+
+use crate::error::{Error, ProcessFailure, Result};
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+/// Timeout for ffprobe calls, which only read container headers.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many stderr lines are kept in a [`ProcessFailure`].
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Runs `command` to completion and captures its stdout and stderr.
+pub async fn run(command: Command, timeout: Option<Duration>) -> Result<Output> {
+    run_with_timeout(command, timeout, None).await
+}
+
+/// Like [`run`], for a program other than ffmpeg/ffprobe, e.g. a whisper or
+/// pyannote script. It failing to start or exiting with a failure status is
+/// reported as the `error` of the step, e.g. [`Error::Transcription`], with
+/// the same details as a failed ffmpeg run.
+pub async fn run_program(
+    command: Command,
+    timeout: Option<Duration>,
+    error: fn(String) -> Error,
+) -> Result<Output> {
+    run(command, timeout).await.map_err(|e| match e {
+        Error::Ffmpeg(message) => error(message),
+        Error::ProcessFailed(mut failure) => {
+            failure.kind = error(String::new()).kind();
+            Error::ProcessFailed(failure)
+        }
+        e => e,
+    })
+}
+
+/// Like [`run`], but hands every stdout line to `on_line` as it arrives
+/// instead of collecting it, so the returned `stdout` is empty.
+pub async fn run_streaming(
+    command: Command,
+    timeout: Option<Duration>,
+    mut on_line: impl FnMut(&str) + Send,
+) -> Result<Output> {
+    run_with_timeout(command, timeout, Some(&mut on_line)).await
+}
+
+type LineHandler<'a> = &'a mut (dyn FnMut(&str) + Send);
+
+async fn run_with_timeout(
+    command: Command,
+    timeout: Option<Duration>,
+    on_line: Option<LineHandler<'_>>,
+) -> Result<Output> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let command_line = command_line(&command);
+    let run = spawn_and_wait(command, &program, on_line);
+
+    let output = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, run).await.map_err(|_| {
+            Error::Ffmpeg(format!("{} timed out after {:?}", program, timeout))
+        })??,
+        None => run.await?,
+    };
+
+    if !output.status.success() {
+        return Err(failure(&program, command_line, &output));
+    }
+    Ok(output)
+}
+
+fn failure(program: &str, command: String, output: &Output) -> Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().filter(|line| !line.trim().is_empty()).collect();
+    let stderr_tail: Vec<String> = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+
+    let exit_code = output.status.code();
+    let status = match exit_code {
+        Some(code) => format!("exited with code {}", code),
+        None => "was killed".to_string(),
+    };
+    let message = match stderr_tail.last() {
+        Some(last) => format!("{} {}: {}", program, status, last),
+        None => format!("{} {}", program, status),
+    };
+
+    Error::ProcessFailed(Box::new(ProcessFailure {
+        message,
+        kind: "ffmpeg",
+        command,
+        exit_code,
+        stderr_tail,
+    }))
+}
+
+/// Renders a command the way it would be typed in a shell, quoting arguments
+/// that contain spaces or quotes.
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains([' ', '"', '\'', ';']) {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+async fn spawn_and_wait(
+    command: Command,
+    program: &str,
+    on_line: Option<LineHandler<'_>>,
+) -> Result<Output> {
+    let mut command = tokio::process::Command::from(command);
+    command
+        .kill_on_drop(true)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| Error::Ffmpeg(format!("Failed to execute {}: {}", program, e)))?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let read_stdout = async {
+        match on_line {
+            Some(on_line) => {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Some(line) = lines.next_line().await? {
+                    on_line(&line);
+                }
+                Ok(Vec::new())
+            }
+            None => read_all(stdout).await,
+        }
+    };
+
+    let (stdout, stderr) = tokio::join!(read_stdout, read_all(stderr));
+    let status = child.wait().await?;
+
+    Ok(Output {
+        status,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+async fn read_all(mut pipe: impl AsyncRead + Unpin) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    pipe.read_to_end(&mut buf).await?;
+    Ok(buf)
+}
@@ -0,0 +1,10 @@
+//! Where projects live on disk: the [`Workspace`] folder layout and the
+//! `project.json` manifest of each project.
+
+mod project;
+mod workspace;
+
+pub use project::{
+    Dimensions, Project, Render, Source, Timing, TranscriptionSettings, MANIFEST_FILE,
+};
+pub use workspace::{Workspace, WORKSPACE_ENV};
@@ -0,0 +1,190 @@
+//! `project.json` manifest kept in every project folder.
+//!
+//! It records where the video came from and the choices made in each step
+//! (timings, crop, transcription, subtitle variant and style, renders) so a
+//! project can be reopened after a restart.
+
+use crate::error::{Error, Result};
+use super::Workspace;
+use crate::transcription::glossary::Term;
+use crate::transcription::BackendConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MANIFEST_FILE: &str = "project.json";
+
+/// Serializes read-modify-write cycles, jobs and the UI may update the same manifest.
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timing {
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Crop rectangle in percent of the source video.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dimensions {
+    pub width: f32,
+    pub height: f32,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Source {
+    /// Projects created before manifests existed.
+    #[default]
+    Unknown,
+    Youtube { url: String },
+    Upload { path: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscriptionSettings {
+    pub language: Option<String>,
+    pub model: Option<String>,
+    /// Backend used for this project, the app default when missing.
+    pub backend: Option<BackendConfig>,
+    /// Cache key of the response the subtitles were built from, see
+    /// `transcription::cache`.
+    pub response: Option<String>,
+    /// Languages of the translated subtitle tracks, see `translation`.
+    pub translations: Vec<String>,
+    /// Terms of this project, added to the global glossary.
+    pub glossary: Vec<Term>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Render {
+    /// File name of the rendered video inside the project folder.
+    pub output: String,
+    pub subtitle_variant: String,
+    pub video_height: i32,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Project {
+    pub id: String,
+    pub source: Source,
+    pub title: Option<String>,
+    /// YouTube channel id, its videos share learned corrections.
+    pub channel: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub timings: Vec<Timing>,
+    pub crop: Option<Dimensions>,
+    pub transcription: Option<TranscriptionSettings>,
+    /// Subtitle variant picked in the UI ("words", "3words", "segments", ...).
+    pub subtitle_variant: Option<String>,
+    /// Cue style as edited in the UI, stored as sent.
+    pub style: Option<Value>,
+    /// Names of the speakers found by `diarization`, by the label the
+    /// diarizer gave them.
+    pub speakers: BTreeMap<String, String>,
+    pub renders: Vec<Render>,
+}
+
+impl Project {
+    pub fn new(id: &str, source: Source) -> Self {
+        let now = now();
+        Project {
+            id: id.to_string(),
+            source,
+            created_at: now,
+            updated_at: now,
+            ..Default::default()
+        }
+    }
+
+    pub fn load(workspace: &Workspace, id: &str) -> Result<Self> {
+        let path = workspace.project_dir(id)?.join(MANIFEST_FILE);
+        let content = fs::read_to_string(&path)
+            .map_err(|_| Error::NotFound(format!("Project {} has no manifest", id)))?;
+        let mut project: Project = serde_json::from_str(&content)
+            .map_err(|e| Error::InvalidInput(format!("Invalid manifest {:?}: {}", path, e)))?;
+        // the folder name wins if the manifest was copied from another project
+        project.id = id.to_string();
+        Ok(project)
+    }
+
+    pub fn save(&mut self, workspace: &Workspace) -> Result<()> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        self.write(workspace)
+    }
+
+    /// Applies `change` to the manifest of `id`, creating it if the project has none yet.
+    pub fn update(
+        workspace: &Workspace,
+        id: &str,
+        change: impl FnOnce(&mut Project),
+    ) -> Result<Project> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        let mut project = match Project::load(workspace, id) {
+            Ok(project) => project,
+            Err(Error::NotFound(_)) => Project::new(id, Source::Unknown),
+            Err(e) => return Err(e),
+        };
+        change(&mut project);
+        project.write(workspace)?;
+        Ok(project)
+    }
+
+    pub fn add_render(&mut self, output: &str, subtitle_variant: &str, video_height: i32) {
+        self.renders.push(Render {
+            output: output.to_string(),
+            subtitle_variant: subtitle_variant.to_string(),
+            video_height,
+            created_at: now(),
+        });
+    }
+
+    /// Every project with a manifest, most recently updated first.
+    pub fn list(workspace: &Workspace) -> Result<Vec<Project>> {
+        let mut projects = Vec::new();
+        for entry in fs::read_dir(workspace.root())? {
+            let entry = entry?;
+            if !entry.path().join(MANIFEST_FILE).is_file() {
+                continue;
+            }
+            let Some(id) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            match Project::load(workspace, &id) {
+                Ok(project) => projects.push(project),
+                Err(e) => eprintln!("Skipping project {}: {}", id, e),
+            }
+        }
+        projects.sort_by_key(|project| std::cmp::Reverse(project.updated_at));
+        Ok(projects)
+    }
+
+    fn write(&mut self, workspace: &Workspace) -> Result<()> {
+        self.updated_at = now();
+        let dir = workspace.project_dir(&self.id)?;
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidInput(format!("Failed to serialize manifest: {}", e)))?;
+
+        // write then rename so a crash never leaves a truncated manifest
+        let tmp = dir.join(format!("{}.tmp", MANIFEST_FILE));
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, dir.join(MANIFEST_FILE))?;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
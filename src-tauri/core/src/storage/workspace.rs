@@ -0,0 +1,101 @@
+use crate::error::{Error, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides the workspace location.
+pub const WORKSPACE_ENV: &str = "YTB_EDITOR_WORKSPACE";
+
+/// Root folder holding one subfolder per project (video id).
+///
+/// Every file the app reads or writes for a project is resolved through here,
+/// e.g. `{root}/{video_id}/{video_id}_trimmed.mp4`.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Workspace { root })
+    }
+
+    /// Picks the workspace root: `YTB_EDITOR_WORKSPACE` first, then the
+    /// `workspace_dir` setting, then `default_root`.
+    pub fn resolve(configured: Option<&Path>, default_root: &Path) -> Result<Self> {
+        match std::env::var_os(WORKSPACE_ENV) {
+            Some(dir) if !dir.is_empty() => Workspace::new(dir),
+            _ => Workspace::new(configured.unwrap_or(default_root)),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Folder of a project, created on first use.
+    pub fn project_dir(&self, video_id: &str) -> Result<PathBuf> {
+        validate_video_id(video_id)?;
+        let dir = self.root.join(video_id);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Path of a project file named `{video_id}{suffix}`, e.g. `file(id, "_trimmed.mp4")`.
+    pub fn file(&self, video_id: &str, suffix: &str) -> Result<PathBuf> {
+        Ok(self
+            .project_dir(video_id)?
+            .join(format!("{}{}", video_id, suffix)))
+    }
+
+    /// Path of a subtitle variant as named by the frontend ("segments", "words", "3words", ...).
+    pub fn subtitle_file(&self, video_id: &str, sub_type: &str) -> Result<PathBuf> {
+        match sub_type {
+            "segments" => self.file(video_id, ".vtt"),
+            _ => self.file(video_id, &format!("_{}.vtt", sub_type)),
+        }
+    }
+
+    /// Path of an existing file inside a project folder, as requested by the webview.
+    ///
+    /// Unlike [`Workspace::file`] this never creates anything and rejects names
+    /// that would escape the project folder.
+    pub fn artifact(&self, video_id: &str, artifact: &str) -> Result<PathBuf> {
+        validate_video_id(video_id)?;
+        let valid = !artifact.is_empty()
+            && !artifact.starts_with('.')
+            && !artifact.contains(['/', '\\', ':']);
+        if !valid {
+            return Err(Error::InvalidInput(format!("Invalid artifact: {:?}", artifact)));
+        }
+
+        let path = self.root.join(video_id).join(artifact);
+        if !path.is_file() {
+            return Err(Error::NotFound(format!("{}/{} does not exist", video_id, artifact)));
+        }
+        Ok(path)
+    }
+
+    /// Deletes a project folder and everything in it.
+    pub fn remove_project(&self, video_id: &str) -> Result<()> {
+        validate_video_id(video_id)?;
+        let dir = self.root.join(video_id);
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_video_id(video_id: &str) -> Result<()> {
+    let valid = !video_id.is_empty()
+        && video_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!("Invalid video id: {:?}", video_id)))
+    }
+}
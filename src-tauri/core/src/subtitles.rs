@@ -0,0 +1,539 @@
+//! WebVTT subtitle variants: generated from a transcription, condensed to
+//! a few words per cue, and converted to ASS for burning in.
+
+use crate::error::{Error, Result};
+use crate::storage::Workspace;
+use rsubs_lib::vtt;
+use serde_json::Value;
+use std::io::{self, BufRead};
+use std::{fs, io::BufReader, path::Path}; // Import the BufRead trait
+
+/// Subtitle variants written by [`write_variants`], as named by
+/// [`Workspace::subtitle_file`].
+pub const SUBTITLE_VARIANTS: &[&str] = &["segments", "words", "3words", "4words", "5words", "6words"];
+const TRANSLATED_PREFIX: &str = "translated_";
+
+/// Fails with [`Error::NotFound`] if the subtitle variant was never generated.
+pub fn check_subtitle_variant(workspace: &Workspace, video_id: &str, sub_type: &str) -> Result<()> {
+    let path = workspace.subtitle_file(video_id, sub_type)?;
+    if !path.is_file() {
+        return Err(Error::NotFound(format!(
+            "Subtitle file does not exist: {:?}",
+            path
+        )));
+    }
+    Ok(())
+}
+
+/// Writes every subtitle variant of a project from a `verbose_json`
+/// transcription with word and segment timestamps.
+pub fn write_variants(workspace: &Workspace, video_id: &str, json_data: &Value) -> Result<()> {
+    for (sub_type, vtt) in variants(json_data) {
+        std::fs::write(workspace.subtitle_file(video_id, sub_type)?, vtt)?;
+    }
+    Ok(())
+}
+
+/// Every subtitle variant of a `verbose_json` transcription, as
+/// [`SUBTITLE_VARIANTS`] names and WebVTT documents.
+pub fn variants(json_data: &Value) -> Vec<(&'static str, String)> {
+    let vtt_words = convert_to_vtt(json_data, "words");
+    let vtt_segments = convert_to_vtt(json_data, "segments");
+    let vtt_3_words = condense_subtitle(&vtt_words, 3).join("\n");
+    let vtt_4_words: String = condense_subtitle(&vtt_words, 4).join("\n");
+    let vtt_5_words: String = condense_subtitle(&vtt_words, 5).join("\n");
+    let vtt_6_words: String = condense_subtitle(&vtt_words, 6).join("\n");
+
+    vec![
+        ("segments", vtt_segments),
+        ("words", vtt_words),
+        ("3words", vtt_3_words),
+        ("4words", vtt_4_words),
+        ("5words", vtt_5_words),
+        ("6words", vtt_6_words),
+    ]
+}
+
+/// Turns the `words` or `segments` of a `verbose_json` transcription into
+/// a WebVTT document with one cue per entry.
+pub fn convert_to_vtt(json_data: &Value, subtitle_type: &str) -> String {
+    let mut vtt_content = String::new();
+    vtt_content.push_str("WEBVTT\n\n");
+
+    let words = json_data.get(subtitle_type).and_then(Value::as_array);
+
+    if let Some(words) = words {
+        let mut subtitle = String::new();
+        let mut word_count = 0;
+
+        for word in words {
+            if let Some(word_map) = word.as_object() {
+                let segment_text = word_map
+                    .get("word")
+                    .or_else(|| word_map.get("text"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("");
+                let start_time = word_map.get("start").and_then(Value::as_f64).unwrap_or(0.0);
+                let end_time = word_map.get("end").and_then(Value::as_f64).unwrap_or(0.0);
+
+                if segment_text.trim().is_empty() {
+                    continue;
+                }
+
+                for word_text in segment_text.split_whitespace() {
+                    subtitle.push_str(word_text);
+                    word_count += 1;
+
+                    if word_count % 3 == 0 {
+                        subtitle.push('\n');
+                    } else {
+                        subtitle.push(' ');
+                    }
+                }
+
+                let start_time_str = format_time(start_time);
+                let end_time_str = format_time(end_time);
+
+                vtt_content.push_str(&format!(
+                    "{} --> {}\n{}\n\n",
+                    start_time_str, end_time_str, subtitle.trim_end()
+                ));
+                subtitle.clear();
+            }
+        }
+    }
+
+    vtt_content
+}
+
+/// `HH:MM:SS.mmm`, as used in WebVTT timestamps.
+pub fn format_time(time: f64) -> String {
+    let hours = (time / 3600.0) as u64;
+    let minutes = ((time / 60.0) % 60.0) as u64;
+    let seconds = (time % 60.0) as u64;
+    let milliseconds = ((time * 1000.0) % 1000.0) as u64;
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hours, minutes, seconds, milliseconds
+    )
+}
+
+/// Seconds of a WebVTT timestamp, `HH:MM:SS.mmm` or `MM:SS.mmm`.
+pub(crate) fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// One cue of a WebVTT document.
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    /// Cue settings after the timings, e.g. `line:80%`, empty when none.
+    pub settings: String,
+    pub text: String,
+}
+
+/// The cues of a WebVTT document. The header, notes and cue identifiers are
+/// dropped.
+pub fn parse_cues(vtt: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut lines = vtt.lines();
+    while let Some(line) = lines.next() {
+        let Some((start, rest)) = line.split_once("-->") else {
+            continue;
+        };
+        let mut rest = rest.split_whitespace();
+        let (Some(start), Some(end)) = (parse_time(start.trim()), rest.next().and_then(parse_time))
+        else {
+            continue;
+        };
+        let settings = rest.collect::<Vec<_>>().join(" ");
+        let text = lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n");
+        cues.push(Cue {
+            start,
+            end,
+            settings,
+            text,
+        });
+    }
+    cues
+}
+
+/// A WebVTT document of `cues`, in the layout [`convert_to_vtt`] writes.
+pub fn write_cues(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for cue in cues {
+        vtt.push_str(&cue_block(cue));
+        vtt.push_str("\n\n");
+    }
+    vtt
+}
+
+fn cue_block(cue: &Cue) -> String {
+    let mut block = format!("{} --> {}", format_time(cue.start), format_time(cue.end));
+    if !cue.settings.is_empty() {
+        block.push(' ');
+        block.push_str(&cue.settings);
+    }
+    block.push('\n');
+    block.push_str(&cue.text);
+    block
+}
+
+/// Replaces the cues of a WebVTT document whose middle falls in
+/// `start..end` by `cues`, keeping everything else as written: the header,
+/// style blocks, notes and the other cues. New cues without settings take
+/// those of the first replaced cue, or of the first cue of the document, so
+/// a position set in the editor carries over.
+pub fn splice_cues(vtt: &str, start: f64, end: f64, cues: &[Cue]) -> String {
+    // blocks before the first cue stay in front, the others are ordered by
+    // time, a note staying after the cue it followed, or its replacement
+    let mut head = Vec::new();
+    let mut timed: Vec<(f64, String)> = Vec::new();
+    let mut replaced_settings = None;
+    let mut first_settings = None;
+    let mut last_end = 0.0;
+    for block in blocks(vtt) {
+        match parse_cues(&block).into_iter().next() {
+            None if timed.is_empty() => head.push(block),
+            None => timed.push((last_end, block)),
+            Some(cue) => {
+                first_settings.get_or_insert_with(|| cue.settings.clone());
+                last_end = cue.end;
+                let middle = (cue.start + cue.end) / 2.0;
+                if middle >= start && middle < end {
+                    replaced_settings.get_or_insert(cue.settings);
+                    continue;
+                }
+                timed.push((cue.start, block));
+            }
+        }
+    }
+
+    let settings = replaced_settings.or(first_settings).unwrap_or_default();
+    for cue in cues {
+        let mut cue = cue.clone();
+        if cue.settings.is_empty() {
+            cue.settings = settings.clone();
+        }
+        timed.push((cue.start, cue_block(&cue)));
+    }
+    timed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    if head.is_empty() {
+        head.push("WEBVTT".to_string());
+    }
+    let mut spliced = head.join("\n\n");
+    for (_, block) in timed {
+        spliced.push_str("\n\n");
+        spliced.push_str(&block);
+    }
+    spliced.push('\n');
+    spliced
+}
+
+/// A WebVTT document with the text of every cue replaced by what `text`
+/// returns for it. Everything else is kept as written.
+pub fn map_cues(vtt: &str, mut text: impl FnMut(&Cue) -> String) -> String {
+    let mut mapped = Vec::new();
+    for block in blocks(vtt) {
+        let Some(cue) = parse_cues(&block).into_iter().next() else {
+            mapped.push(block);
+            continue;
+        };
+        // the identifier, if any, and the timings
+        let header: Vec<&str> = block
+            .lines()
+            .take_while(|line| !line.contains("-->"))
+            .chain(block.lines().find(|line| line.contains("-->")))
+            .collect();
+        mapped.push(format!("{}\n{}", header.join("\n"), text(&cue)));
+    }
+    format!("{}\n", mapped.join("\n\n"))
+}
+
+/// The blocks of a WebVTT document, separated by blank lines.
+fn blocks(vtt: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+    for line in vtt.lines() {
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block).join("\n"));
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block.join("\n"));
+    }
+    blocks
+}
+
+/// The speaker and the rest of a cue text starting with a voice span,
+/// `<v Speaker 1>text`.
+pub fn split_voice(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.trim_start().strip_prefix("<v ")?.split_once('>')?;
+    Some((name.trim(), rest.strip_suffix("</v>").unwrap_or(rest)))
+}
+
+/// A cue text without its voice span.
+pub fn strip_voice(text: &str) -> &str {
+    split_voice(text).map_or(text, |(_, rest)| rest)
+}
+
+/// A cue text spoken by `speaker`, replacing its voice span if it has one.
+pub fn with_voice(text: &str, speaker: &str) -> String {
+    format!("<v {}>{}", speaker, strip_voice(text))
+}
+
+/// `text` on one line per `n` words, like the cues of [`convert_to_vtt`].
+pub fn wrap_words(text: &str, n: usize) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words
+        .chunks(n)
+        .map(|chunk| chunk.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Variant name of the subtitle track translated to `language`, stored as
+/// `{id}_translated_{language}.vtt`.
+pub fn translated_variant(language: &str) -> String {
+    format!("{}{}", TRANSLATED_PREFIX, language)
+}
+
+pub fn is_translated_variant(variant: &str) -> bool {
+    variant.starts_with(TRANSLATED_PREFIX)
+}
+
+/// Deletes the generated subtitle files of a project, translated tracks
+/// included. Missing files are ignored.
+pub fn remove_subtitles(workspace: &Workspace, video_id: &str) -> Result<()> {
+    let mut paths = vec![
+        workspace.file(video_id, ".vtt")?,
+        workspace.file(video_id, "_words.vtt")?,
+        workspace.file(video_id, "_3words.vtt")?,
+        workspace.file(video_id, "_4words.vtt")?,
+        workspace.file(video_id, "_segments.vtt")?,
+        workspace.file(video_id, "_pixel.vtt")?,
+    ];
+    let translated = format!("{}_{}", video_id, translated_variant(""));
+    for entry in fs::read_dir(workspace.project_dir(video_id)?)? {
+        let path = entry?.path();
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        if name.is_some_and(|name| name.starts_with(&translated) && name.ends_with(".vtt")) {
+            paths.push(path);
+        }
+    }
+
+    for path in paths {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Error removing file: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the cues of a one-word-per-cue WebVTT document into cues of
+/// `words_per_line` words. Returns the lines of the new document.
+pub fn condense_subtitle(subtitle_text: &str, words_per_line: usize) -> Vec<String> {
+    let mut lines = subtitle_text.lines();
+    let mut condensed_lines: Vec<String> = Vec::new();
+    let mut current_line: Vec<String> = Vec::new();
+    let mut start_time = String::new();
+    let mut end_time = String::new();
+    let mut text_on_next_line = false;
+
+    // Add "WEBVTT" without changes if present
+    if let Some(first_line) = lines.next() {
+        if first_line.trim() == "WEBVTT" {
+            condensed_lines.push(first_line.to_owned());
+            condensed_lines.push("".to_owned());
+            // Ignore the following empty line after "WEBVTT"
+            lines.next();
+        }
+    }
+
+    for line in lines {
+        if text_on_next_line {
+            current_line.push(line.trim().to_owned());
+            text_on_next_line = false;
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() >= 3 {
+            // Assuming the line is a timestamp line
+            if current_line.len() >= words_per_line {
+                let formatted_text = insert_line_breaks(&current_line, 3);
+                condensed_lines.push(format!("{} --> {}", start_time, end_time));
+                condensed_lines.push(formatted_text);
+                condensed_lines.push("".to_owned());
+                current_line.clear();
+            }
+
+            if current_line.is_empty() {
+                start_time = parts[0].to_owned();
+            }
+            end_time = parts[2].to_owned();
+            text_on_next_line = true;
+        }
+    }
+
+    // Handle any remaining text
+    if !current_line.is_empty() {
+        let formatted_text = insert_line_breaks(&current_line, 3);
+        condensed_lines.push(format!("{} --> {}", start_time, end_time));
+        condensed_lines.push(formatted_text);
+    }
+
+    condensed_lines
+}
+
+// Helper function to insert line breaks every `n` words
+fn insert_line_breaks(words: &[String], n: usize) -> String {
+    words.chunks(n).map(|chunk| chunk.join(" ")).collect::<Vec<_>>().join(" \n")
+}
+
+fn vtt_line_to_pixel(
+    workspace: &Workspace,
+    video_id: &str,
+    path: &Path,
+    video_height: i32,
+) -> Result<String> {
+    let vtt_content = std::fs::read_to_string(path)?;
+    let vtt_lines = vtt_content.lines();
+
+    let output_path = workspace.file(video_id, "_pixel.vtt")?;
+
+    let mut output = String::new();
+    // voice spans would be burned in as written, the speaker is shown as a
+    // `Name: ` prefix instead, when it changes
+    let mut speaker = String::new();
+
+    // Iterate through the cue lines
+    for line in vtt_lines {
+        if let Some((name, text)) = split_voice(line) {
+            if name != speaker {
+                speaker = name.to_string();
+                output.push_str(&format!("{}: ", name));
+            }
+            output.push_str(text);
+            output.push('\n');
+            continue;
+        }
+        if line.contains("-->") && line.contains("line:") {
+            let parts: Vec<&str> = line.split("line:").collect();
+            let percentage: f32 = parts[1]
+                .trim_start()
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|e| Error::InvalidInput(format!("Error parsing percentage: {}", e)))?;
+            let pixels = ((1.0 - percentage / 92.0) * video_height as f32) as i32;
+            let new_line = format!("{}line:{}%", parts[0], pixels);
+            output.push_str(&new_line);
+            output.push('\n');
+        } else {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    std::fs::write(&output_path, output)?;
+
+    Ok(output_path.to_string_lossy().into_owned())
+}
+
+/// Converts a subtitle variant to `{id}.ass` for ffmpeg, turning the cue
+/// `line:` percentages into pixels for a video `video_height` tall and
+/// voice spans into the speaker's name.
+pub fn vtt_to_ass(
+    workspace: &Workspace,
+    video_id: &str,
+    sub_type: &str,
+    video_height: i32,
+) -> Result<()> {
+    let output_path = workspace.file(video_id, ".ass")?;
+
+    // Try to delete the file if it exists, ignore the error if it does not
+    let _ = fs::remove_file(&output_path);
+
+    let path = workspace.subtitle_file(video_id, sub_type)?;
+    let pixel_path = vtt_line_to_pixel(workspace, video_id, &path, video_height)?;
+
+    let ass_content = vtt::parse(pixel_path)
+        .map_err(|err| Error::InvalidInput(format!("Failed to parse VTT file: {}", err)))?
+        .to_ass();
+
+    // Save the initial ass_content
+    let output_str = output_path
+        .to_str()
+        .ok_or_else(|| Error::InvalidInput(format!("Invalid path: {:?}", output_path)))?;
+    ass_content
+        .to_file(output_str)
+        .map_err(|err| Error::Io(format!("Failed to write ASS file: {}", err)))?;
+
+    // Open the saved file
+    let file = fs::File::open(&output_path)?;
+    let reader = BufReader::new(file);
+    let resolution_line_prefix = "PlayResY: ";
+    let dialogue_line_prefix = "Dialogue:";
+    let mut modified_content = String::new();
+    let lines = reader.lines().collect::<Result<Vec<_>, io::Error>>()?;
+
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].starts_with(dialogue_line_prefix)
+            && i + 1 < lines.len()
+            && !lines[i + 1].starts_with(dialogue_line_prefix)
+            && !lines[i + 1].is_empty()
+        {
+            // Merge current dialogue line with the next line, appending \N in between
+            let merged_line = format!("{}\\N{}", lines[i], lines[i + 1]);
+            modified_content.push_str(&merged_line);
+            modified_content.push('\n');
+            // Skip the next line since it's already appended
+            i += 2;
+            continue;
+        } else if lines[i].starts_with(resolution_line_prefix) {
+            // Replace the resolution value with video_height
+            let new_line = format!("{}{}", resolution_line_prefix, video_height);
+            modified_content.push_str(&new_line);
+            modified_content.push('\n');
+        } else {
+            // Normal line processing
+            modified_content.push_str(&lines[i]);
+            modified_content.push('\n');
+        }
+        i += 1;
+    }
+
+    // If the resolution line was not found, append it to the end
+    if !modified_content.contains(resolution_line_prefix) {
+        let new_line = format!("{}{}", resolution_line_prefix, video_height);
+        modified_content.push_str(&new_line);
+        modified_content.push('\n');
+    }
+
+    // Write the modified content back to the file
+    fs::write(&output_path, modified_content)?;
+
+    Ok(())
+}
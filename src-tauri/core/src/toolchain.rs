@@ -0,0 +1,336 @@
+//! Locating and checking the ffmpeg and ffprobe binaries.
+//!
+//! Each binary is looked up in order: the path configured with [`configure`],
+//! a sidecar shipped next to the current executable, then `PATH`. Commands
+//! built with [`ffmpeg`] and [`ffprobe`] use whatever was found. [`check`]
+//! runs the binaries to read their versions and the filters and encoders the
+//! pipeline relies on; [`require`] turns a failed check into an error before
+//! a step starts.
+
+use crate::error::{Error, Result};
+use crate::runner;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// Oldest ffmpeg major version that reports `out_time_us` progress.
+pub const MIN_MAJOR_VERSION: u32 = 4;
+
+/// Paths picked by the user, each one wins over the sidecar and `PATH`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub ffmpeg: Option<PathBuf>,
+    pub ffprobe: Option<PathBuf>,
+}
+
+/// Something a pipeline step needs from the ffmpeg build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Feature {
+    /// The `subtitles` filter, only present in builds with libass.
+    Libass,
+    /// The libmp3lame encoder, used for the audio sent to transcription.
+    Libmp3lame,
+    /// The libx264 encoder, used for every re-encoded video.
+    Libx264,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::Libass, Feature::Libmp3lame, Feature::Libx264];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Feature::Libass => "libass (subtitles filter), needed to burn subtitles",
+            Feature::Libmp3lame => "libmp3lame encoder, needed to extract audio",
+            Feature::Libx264 => "libx264 encoder, needed to trim and render videos",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BinarySource {
+    Settings,
+    Sidecar,
+    Path,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryReport {
+    pub path: Option<PathBuf>,
+    pub source: Option<BinarySource>,
+    /// As printed by `-version`, e.g. "6.1.1".
+    pub version: Option<String>,
+    /// Why the binary can't be used, `None` when it can.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureReport {
+    pub feature: Feature,
+    pub description: &'static str,
+    pub available: bool,
+}
+
+/// Result of [`check`], readable enough to show as is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentReport {
+    pub ffmpeg: BinaryReport,
+    pub ffprobe: BinaryReport,
+    pub features: Vec<FeatureReport>,
+    /// Both binaries work and every feature is available.
+    pub ok: bool,
+}
+
+impl EnvironmentReport {
+    /// The problems that would make a step needing `features` fail.
+    pub fn problems(&self, features: &[Feature]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for binary in [&self.ffmpeg, &self.ffprobe] {
+            if let Some(error) = &binary.error {
+                problems.push(error.clone());
+            }
+        }
+        if self.ffmpeg.error.is_none() {
+            for report in &self.features {
+                if features.contains(&report.feature) && !report.available {
+                    problems.push(format!("ffmpeg is missing {}", report.description));
+                }
+            }
+        }
+        problems
+    }
+}
+
+struct State {
+    overrides: Overrides,
+    report: Option<EnvironmentReport>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    overrides: Overrides {
+        ffmpeg: None,
+        ffprobe: None,
+    },
+    report: None,
+});
+
+/// Sets the user's binary paths and forgets the last check.
+pub fn configure(overrides: Overrides) {
+    let mut state = STATE.lock().unwrap();
+    if state.overrides != overrides {
+        state.overrides = overrides;
+        state.report = None;
+    }
+}
+
+/// A `Command` for the ffmpeg binary in use.
+pub fn ffmpeg() -> Command {
+    Command::new(binary_path("ffmpeg"))
+}
+
+/// A `Command` for the ffprobe binary in use.
+pub fn ffprobe() -> Command {
+    Command::new(binary_path("ffprobe"))
+}
+
+fn binary_path(name: &str) -> PathBuf {
+    let overrides = STATE.lock().unwrap().overrides.clone();
+    match locate(name, &overrides) {
+        Some((path, _)) => path,
+        // let the spawn fail with the usual "not found" error
+        None => PathBuf::from(name),
+    }
+}
+
+fn locate(name: &str, overrides: &Overrides) -> Option<(PathBuf, BinarySource)> {
+    let configured = match name {
+        "ffmpeg" => overrides.ffmpeg.as_ref(),
+        _ => overrides.ffprobe.as_ref(),
+    };
+    if let Some(path) = configured {
+        return Some((path.clone(), BinarySource::Settings));
+    }
+
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    let sidecar = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(&file_name)))
+        .filter(|path| path.is_file());
+    if let Some(path) = sidecar {
+        return Some((path, BinarySource::Sidecar));
+    }
+
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+            .map(|path| (path, BinarySource::Path))
+    })
+}
+
+/// Runs both binaries and reports what was found. The result is cached until
+/// the next [`configure`] with different paths.
+pub async fn check() -> EnvironmentReport {
+    let overrides = STATE.lock().unwrap().overrides.clone();
+
+    let (ffmpeg, ffprobe) = tokio::join!(
+        check_binary("ffmpeg", &overrides),
+        check_binary("ffprobe", &overrides)
+    );
+    let features = match (&ffmpeg.path, &ffmpeg.error) {
+        (Some(path), None) => check_features(path).await,
+        _ => Feature::ALL
+            .iter()
+            .map(|&feature| FeatureReport {
+                feature,
+                description: feature.describe(),
+                available: false,
+            })
+            .collect(),
+    };
+    let ok = ffmpeg.error.is_none()
+        && ffprobe.error.is_none()
+        && features.iter().all(|report| report.available);
+    let report = EnvironmentReport {
+        ffmpeg,
+        ffprobe,
+        features,
+        ok,
+    };
+
+    let mut state = STATE.lock().unwrap();
+    if state.overrides == overrides {
+        state.report = Some(report.clone());
+    }
+    report
+}
+
+/// Fails with [`Error::Environment`] if ffmpeg or ffprobe is unusable or
+/// ffmpeg lacks one of `features`. A cached report is trusted only when it
+/// has no problems, so fixing the install does not need a restart.
+pub async fn require(features: &[Feature]) -> Result<()> {
+    let cached = STATE.lock().unwrap().report.clone();
+    if let Some(report) = cached {
+        if report.problems(features).is_empty() {
+            return Ok(());
+        }
+    }
+
+    let problems = check().await.problems(features);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Environment(problems.join("; ")))
+    }
+}
+
+async fn check_binary(name: &str, overrides: &Overrides) -> BinaryReport {
+    let Some((path, source)) = locate(name, overrides) else {
+        return BinaryReport {
+            path: None,
+            source: None,
+            version: None,
+            error: Some(format!(
+                "{} was not found, install it or set its path in the settings",
+                name
+            )),
+        };
+    };
+
+    let mut report = BinaryReport {
+        path: Some(path.clone()),
+        source: Some(source),
+        version: None,
+        error: None,
+    };
+    if !path.is_file() {
+        report.error = Some(format!("{} {:?} does not exist", name, path));
+        return report;
+    }
+
+    let mut command = Command::new(&path);
+    command.args(["-hide_banner", "-version"]);
+    match runner::run(command, Some(runner::PROBE_TIMEOUT)).await {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            report.version = parse_version(&stdout);
+            match report.version.as_deref().and_then(major_version) {
+                Some(major) if major < MIN_MAJOR_VERSION => {
+                    report.error = Some(format!(
+                        "{} {} is too old, {}.0 or newer is required",
+                        name,
+                        report.version.as_deref().unwrap_or_default(),
+                        MIN_MAJOR_VERSION
+                    ));
+                }
+                // git builds ("N-113542-g...") carry no release number
+                _ => {}
+            }
+        }
+        Err(e) => report.error = Some(e.message().to_string()),
+    }
+    report
+}
+
+async fn check_features(ffmpeg: &Path) -> Vec<FeatureReport> {
+    let list = |kind: &str| {
+        let mut command = Command::new(ffmpeg);
+        command.args(["-hide_banner", kind]);
+        runner::run(command, Some(runner::PROBE_TIMEOUT))
+    };
+    let (filters, encoders) = tokio::join!(list("-filters"), list("-encoders"));
+    let filters = names(filters);
+    let encoders = names(encoders);
+
+    Feature::ALL
+        .iter()
+        .map(|&feature| {
+            let available = match feature {
+                Feature::Libass => filters.iter().any(|name| name == "subtitles"),
+                Feature::Libmp3lame => encoders.iter().any(|name| name == "libmp3lame"),
+                Feature::Libx264 => encoders.iter().any(|name| name == "libx264"),
+            };
+            FeatureReport {
+                feature,
+                description: feature.describe(),
+                available,
+            }
+        })
+        .collect()
+}
+
+/// Names listed by `-filters`/`-encoders`, the second column of each line.
+fn names(output: Result<std::process::Output>) -> Vec<String> {
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+/// "ffmpeg version 6.1.1-3ubuntu5 Copyright ..." -> "6.1.1-3ubuntu5"
+fn parse_version(output: &str) -> Option<String> {
+    let first = output.lines().next()?;
+    let mut words = first.split_whitespace();
+    words.find(|word| *word == "version")?;
+    words.next().map(str::to_string)
+}
+
+/// Major version of "6.1.1", "n6.1" or "4.4.2-0ubuntu0.22.04.1".
+fn major_version(version: &str) -> Option<u32> {
+    let version = version.strip_prefix('n').unwrap_or(version);
+    let digits: String = version.chars().take_while(char::is_ascii_digit).collect();
+    if version[digits.len()..].starts_with('.') {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
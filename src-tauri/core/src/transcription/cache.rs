@@ -0,0 +1,80 @@
+//! Raw transcriptions kept in the project folder, so subtitles can be rebuilt
+//! and the same audio transcribed again without calling the backend.
+//!
+//! Each response is stored as `{project}/transcriptions/{key}.json`, where
+//! the key is a SHA-256 of the audio and of every request parameter that
+//! changes the result.
+
+use crate::error::{Error, Result};
+use crate::storage::Workspace;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CACHE_DIR: &str = "transcriptions";
+
+/// What a transcription depends on, besides the audio.
+pub struct Params<'a> {
+    /// See [`super::Backend::fingerprint`].
+    pub backend: &'a str,
+    pub language: &'a str,
+    /// See [`super::glossary::prompt`], empty without one.
+    pub prompt: &'a str,
+    /// Chunk size the audio was split at, `None` when sent whole.
+    pub max_chunk_bytes: Option<u64>,
+}
+
+/// Hex SHA-256 of the audio file and `params`.
+pub fn key(audio: &Path, params: &Params) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(audio)?, &mut hasher)?;
+    // separated by NUL so no two parameter lists hash the same
+    let max_chunk_bytes = params
+        .max_chunk_bytes
+        .map(|bytes| bytes.to_string())
+        .unwrap_or_default();
+    for part in [params.backend, params.language, params.prompt, &max_chunk_bytes] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// The cached response for `key`, `None` when there is none or it is unreadable.
+pub fn load(workspace: &Workspace, video_id: &str, key: &str) -> Result<Option<Value>> {
+    let path = path(workspace, video_id, key)?;
+    if !path.is_file() {
+        return Ok(None);
+    }
+    match serde_json::from_str(&fs::read_to_string(&path)?) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            eprintln!("Ignoring unreadable cached transcription {:?}: {}", path, e);
+            Ok(None)
+        }
+    }
+}
+
+pub fn store(workspace: &Workspace, video_id: &str, key: &str, response: &Value) -> Result<()> {
+    let path = path(workspace, video_id, key)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string(response)
+        .map_err(|e| Error::Transcription(format!("Failed to serialize transcription: {}", e)))?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+fn path(workspace: &Workspace, video_id: &str, key: &str) -> Result<PathBuf> {
+    Ok(workspace
+        .project_dir(video_id)?
+        .join(CACHE_DIR)
+        .join(format!("{}.json", key)))
+}
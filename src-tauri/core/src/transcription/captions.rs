@@ -0,0 +1,192 @@
+//! Existing transcripts and caption files, turned into the `verbose_json`
+//! shape the subtitle variants are built from.
+//!
+//! Caption cues become the segments. Captions have no word timestamps, so
+//! every cue's duration is shared between its words by their length.
+
+use super::check_timestamps;
+use crate::error::{Error, Result};
+use crate::subtitles::{self, parse_time};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Format {
+    Srt,
+    Vtt,
+    /// YouTube's SubViewer captions.
+    Sbv,
+    /// A Whisper or OpenAI `verbose_json` transcription.
+    VerboseJson,
+}
+
+impl Format {
+    /// The format of `file`, from its extension or else its content.
+    pub fn detect(file: &Path, content: &str) -> Result<Self> {
+        let extension = file
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("srt") => return Ok(Format::Srt),
+            Some("vtt") => return Ok(Format::Vtt),
+            Some("sbv") => return Ok(Format::Sbv),
+            Some("json") => return Ok(Format::VerboseJson),
+            _ => {}
+        }
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+        let first_line = content.lines().next().unwrap_or_default();
+        if content.starts_with('{') {
+            Ok(Format::VerboseJson)
+        } else if first_line.starts_with("WEBVTT") {
+            Ok(Format::Vtt)
+        } else if content.contains("-->") {
+            Ok(Format::Srt)
+        } else if first_line.contains(',') && first_line.split(',').all(|time| parse_time(time).is_some()) {
+            Ok(Format::Sbv)
+        } else {
+            Err(Error::InvalidInput(format!(
+                "Unknown transcript format {:?}, expected SRT, VTT, SBV or verbose_json",
+                file
+            )))
+        }
+    }
+}
+
+/// A `verbose_json` transcription of `content`, with word and segment
+/// timestamps.
+pub fn parse(content: &str, format: Format) -> Result<Value> {
+    let content = content.trim_start_matches('\u{feff}');
+    let cues = match format {
+        Format::VerboseJson => return parse_verbose_json(content),
+        Format::Vtt => subtitles::parse_cues(content)
+            .into_iter()
+            .map(|cue| (cue.start, cue.end, cue.text))
+            .collect(),
+        Format::Srt => parse_blocks(content, |line| {
+            let (start, end) = line.split_once("-->")?;
+            // `00:00:01,000 --> 00:00:02,000 X1:...`, with optional coordinates
+            let end = end.split_whitespace().next()?;
+            Some((parse_time(&start.trim().replace(',', "."))?, parse_time(&end.replace(',', "."))?))
+        }),
+        Format::Sbv => parse_blocks(content, |line| {
+            let (start, end) = line.split_once(',')?;
+            Some((parse_time(start.trim())?, parse_time(end.trim())?))
+        }),
+    };
+    let cues: Vec<(f64, f64, String)> = cues
+        .into_iter()
+        .map(|(start, end, text)| (start, end, plain_text(&text)))
+        .filter(|(start, end, text)| end >= start && !text.is_empty())
+        .collect();
+    if cues.is_empty() {
+        return Err(Error::InvalidInput("The transcript has no cues".to_string()));
+    }
+    Ok(from_segments(&cues, Value::Null))
+}
+
+/// Cues of the blocks separated by blank lines, `timings` parsing the line
+/// with the start and end. Lines before it, e.g. SRT numbers, are skipped.
+fn parse_blocks(content: &str, timings: impl Fn(&str) -> Option<(f64, f64)>) -> Vec<(f64, f64, String)> {
+    let mut cues = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some((start, end)) = timings(line.trim()) else {
+            continue;
+        };
+        let text = lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ");
+        cues.push((start, end, text));
+    }
+    cues
+}
+
+/// A transcription from another Whisper tool. Word timestamps are made up
+/// from the segments when it has none.
+fn parse_verbose_json(content: &str) -> Result<Value> {
+    let mut transcription: Value = serde_json::from_str(content)
+        .map_err(|e| Error::InvalidInput(format!("Invalid verbose_json transcript: {}", e)))?;
+    if !transcription["segments"].is_array() {
+        return Err(Error::InvalidInput(
+            "The transcript has no segments, expected a verbose_json transcription".to_string(),
+        ));
+    }
+    if transcription["words"].is_array() {
+        return check_timestamps(transcription);
+    }
+    let segments: Vec<(f64, f64, String)> = transcription["segments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|segment| {
+            Some((
+                segment["start"].as_f64()?,
+                segment["end"].as_f64()?,
+                segment["text"].as_str()?.trim().to_string(),
+            ))
+        })
+        .collect();
+    Ok(from_segments(&segments, transcription["language"].take()))
+}
+
+/// A transcription with one segment per cue and the cue durations shared
+/// between their words, by length.
+fn from_segments(cues: &[(f64, f64, String)], language: Value) -> Value {
+    let mut words = Vec::new();
+    let mut segments = Vec::new();
+    for (start, end, text) in cues {
+        let cue_words: Vec<&str> = text.split_whitespace().collect();
+        // a word and the space after it
+        let total: usize = cue_words.iter().map(|word| word.chars().count() + 1).sum();
+        let mut time = *start;
+        for word in cue_words {
+            let duration = (end - start) * (word.chars().count() + 1) as f64 / total as f64;
+            words.push(json!({ "word": word, "start": time, "end": time + duration }));
+            time += duration;
+        }
+        segments.push(json!({
+            "id": segments.len(),
+            "start": start,
+            "end": end,
+            "text": format!(" {}", text),
+        }));
+    }
+    let text: Vec<&str> = cues.iter().map(|(_, _, text)| text.as_str()).collect();
+    json!({
+        "task": "transcribe",
+        "language": language,
+        "duration": cues.last().map_or(0.0, |(_, end, _)| *end),
+        "text": text.join(" "),
+        "words": words,
+        "segments": segments,
+    })
+}
+
+/// Cue text on one line, without markup: voice spans, `<i>` tags, ASS
+/// overrides such as `{\an8}` and the common HTML entities.
+fn plain_text(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut closing = None;
+    for c in text.chars() {
+        match closing {
+            Some(end) if c == end => closing = None,
+            Some(_) => {}
+            None if c == '<' => closing = Some('>'),
+            None if c == '{' => closing = Some('}'),
+            None => plain.push(c),
+        }
+    }
+    plain
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,235 @@
+//! Splitting long audio on silence so each piece fits an upload limit, and
+//! stitching the transcriptions of the pieces back together.
+
+use crate::error::{Error, Result};
+use crate::probe;
+use crate::runner;
+use crate::toolchain;
+use crate::Observer;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Quietest level considered silence by ffmpeg's `silencedetect`.
+const SILENCE_NOISE: &str = "-30dB";
+/// Shortest pause, in seconds, worth cutting on.
+const SILENCE_MIN_DURATION: f64 = 0.4;
+/// Chunks aim below the limit, mp3 bitrates vary along the file.
+const SIZE_MARGIN: f64 = 0.9;
+/// Smallest chunk size accepted, about a minute of 128 kbps mp3.
+pub const MIN_CHUNK_BYTES: u64 = 1024 * 1024;
+/// Shortest piece [`plan`] cuts, whatever the limit.
+const MIN_CHUNK_SECONDS: f64 = 1.0;
+
+/// A piece of the audio, `start` and `end` in seconds of the whole file.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub start: f64,
+    pub end: f64,
+    pub path: PathBuf,
+}
+
+/// Where to cut a `duration` seconds file so no piece is longer than
+/// `max_seconds`: on the last pause in the second half of each piece, or
+/// right at `max_seconds` when there is none. The file is one piece when
+/// `max_seconds` is not positive, and pieces are at least
+/// [`MIN_CHUNK_SECONDS`] long.
+pub fn plan(duration: f64, silences: &[(f64, f64)], max_seconds: f64) -> Vec<(f64, f64)> {
+    let mut ranges = Vec::new();
+    let mut start = 0.0;
+    if max_seconds.is_nan() || max_seconds <= 0.0 {
+        return vec![(start, duration)];
+    }
+    let max_seconds = max_seconds.max(MIN_CHUNK_SECONDS);
+    while duration - start > max_seconds {
+        let limit = start + max_seconds;
+        let cut = silences
+            .iter()
+            .map(|(silence_start, silence_end)| (silence_start + silence_end) / 2.0)
+            .rev()
+            .find(|middle| *middle > start + max_seconds / 2.0 && *middle <= limit)
+            .unwrap_or(limit);
+        // too small a step to move `start` forward
+        if cut <= start {
+            break;
+        }
+        ranges.push((start, cut));
+        start = cut;
+    }
+    ranges.push((start, duration));
+    ranges
+}
+
+/// Pauses in the audio as `(start, end)` seconds, from ffmpeg's `silencedetect`.
+pub async fn detect_silences(audio: &Path) -> Result<Vec<(f64, f64)>> {
+    let mut command = toolchain::ffmpeg();
+    command
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(audio)
+        .args([
+            "-af",
+            &format!("silencedetect=noise={}:d={}", SILENCE_NOISE, SILENCE_MIN_DURATION),
+            "-f",
+            "null",
+            "-",
+        ]);
+    let output = runner::run(command, None).await?;
+
+    // [silencedetect @ 0x...] silence_start: 12.345
+    // [silencedetect @ 0x...] silence_end: 13.1 | silence_duration: 0.755
+    let value = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    let mut silences = Vec::new();
+    let mut start = None;
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        if let Some(time) = value(line, "silence_start: ") {
+            start = Some(time);
+        } else if let Some(end) = value(line, "silence_end: ") {
+            if let Some(start) = start.take() {
+                silences.push((start.max(0.0), end));
+            }
+        }
+    }
+    Ok(silences)
+}
+
+/// Fails with [`Error::InvalidInput`] when `max_bytes` is below
+/// [`MIN_CHUNK_BYTES`].
+pub fn check_max_bytes(max_bytes: u64) -> Result<u64> {
+    if max_bytes < MIN_CHUNK_BYTES {
+        return Err(Error::InvalidInput(format!(
+            "Chunks of {} bytes are too small, the minimum is {} bytes (1 MB)",
+            max_bytes, MIN_CHUNK_BYTES
+        )));
+    }
+    Ok(max_bytes)
+}
+
+/// Cuts `audio` into pieces smaller than `max_bytes`, written next to it as
+/// `{stem}_chunk{n}.mp3` and registered with `observer` as they are made.
+/// Returns the file itself as the only chunk, without probing it, when it is
+/// small enough already; its `end` is then [`f64::INFINITY`].
+pub async fn split(audio: &Path, max_bytes: u64, observer: &dyn Observer) -> Result<Vec<Chunk>> {
+    check_max_bytes(max_bytes)?;
+    let size = std::fs::metadata(audio)?.len();
+    if size <= max_bytes {
+        return Ok(vec![Chunk {
+            start: 0.0,
+            end: f64::INFINITY,
+            path: audio.to_path_buf(),
+        }]);
+    }
+    let duration = probe::probe(audio)
+        .await?
+        .duration
+        .ok_or_else(|| Error::Ffmpeg(format!("Failed to get duration of {:?}", audio)))?;
+
+    let max_seconds = duration * max_bytes as f64 * SIZE_MARGIN / size as f64;
+    let silences = detect_silences(audio).await?;
+    let ranges = plan(duration, &silences, max_seconds);
+    eprintln!(
+        "Splitting {:?} ({} bytes, {:.0}s) into {} chunks",
+        audio,
+        size,
+        duration,
+        ranges.len()
+    );
+
+    let stem = audio
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut chunks = Vec::new();
+    for (i, (start, end)) in ranges.into_iter().enumerate() {
+        let path = audio.with_file_name(format!("{}_chunk{}.mp3", stem, i));
+        observer.output(&path);
+        let result = extract(audio, start, end, &path).await;
+        chunks.push(Chunk { start, end, path });
+        if let Err(e) = result {
+            remove(&chunks);
+            return Err(e);
+        }
+    }
+    Ok(chunks)
+}
+
+/// Copies `start..end` seconds of the mp3 `audio` to `path`, without
+/// re-encoding.
+pub async fn extract(audio: &Path, start: f64, end: f64, path: &Path) -> Result<()> {
+    let mut command = toolchain::ffmpeg();
+    command
+        .args(["-y", "-v", "error", "-ss", &start.to_string(), "-t"])
+        .arg((end - start).to_string())
+        .arg("-i")
+        .arg(audio)
+        .args(["-vn", "-c:a", "copy"])
+        .arg(path);
+    runner::run(command, None).await?;
+    Ok(())
+}
+
+/// Deletes the chunk files made by [`split`], leaving the original audio.
+pub fn remove(chunks: &[Chunk]) {
+    if chunks.len() < 2 {
+        return;
+    }
+    for chunk in chunks {
+        if let Err(e) = std::fs::remove_file(&chunk.path) {
+            eprintln!("Error removing chunk {:?}: {}", chunk.path, e);
+        }
+    }
+}
+
+/// Joins the transcriptions of consecutive chunks into one, shifting the
+/// word and segment times by each chunk's start.
+pub fn stitch(parts: Vec<(&Chunk, Value)>) -> Value {
+    let mut text = Vec::new();
+    let mut words = Vec::new();
+    let mut segments = Vec::new();
+    let mut language = Value::Null;
+    let mut duration = 0.0;
+
+    for (chunk, mut part) in parts {
+        if language.is_null() {
+            language = part["language"].take();
+        }
+        if let Some(part_text) = part["text"].as_str() {
+            text.push(part_text.trim().to_string());
+        }
+        for mut word in take_array(&mut part, "words") {
+            shift(&mut word, chunk.start);
+            words.push(word);
+        }
+        for mut segment in take_array(&mut part, "segments") {
+            shift(&mut segment, chunk.start);
+            segment["id"] = json!(segments.len());
+            segments.push(segment);
+        }
+        duration = chunk.end;
+    }
+
+    json!({
+        "task": "transcribe",
+        "language": language,
+        "duration": duration,
+        "text": text.join(" "),
+        "words": words,
+        "segments": segments,
+    })
+}
+
+fn take_array(value: &mut Value, key: &str) -> Vec<Value> {
+    match value[key].take() {
+        Value::Array(items) => items,
+        _ => Vec::new(),
+    }
+}
+
+fn shift(item: &mut Value, offset: f64) {
+    for key in ["start", "end"] {
+        if let Some(time) = item[key].as_f64() {
+            item[key] = json!(time + offset);
+        }
+    }
+}
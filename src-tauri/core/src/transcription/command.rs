@@ -0,0 +1,66 @@
+//! Transcription by an external program, e.g. a local whisper script.
+
+use super::{Backend, BackendFuture, Request};
+use crate::error::{Error, Result};
+use crate::runner;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Runs `program` with `args`, where `{audio}` is replaced by the mp3 path,
+/// `{language}` by the language code, or `auto` when it should be detected,
+/// and `{prompt}` by the glossary prompt or nothing. The program must print a
+/// `verbose_json` shaped transcription on stdout and exit with status 0.
+pub struct CommandBackend {
+    program: PathBuf,
+    args: Vec<String>,
+    model: String,
+}
+
+impl CommandBackend {
+    pub fn new(program: PathBuf, args: Vec<String>) -> Self {
+        let model = program
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| program.to_string_lossy().into_owned());
+        CommandBackend {
+            program,
+            args,
+            model,
+        }
+    }
+
+    async fn run(&self, request: &Request<'_>) -> Result<Value> {
+        let audio = request.audio.to_string_lossy();
+        let mut command = Command::new(&self.program);
+        for arg in &self.args {
+            command.arg(
+                arg.replace("{audio}", &audio)
+                    .replace("{language}", request.language.unwrap_or("auto"))
+                    .replace("{prompt}", request.prompt.unwrap_or_default()),
+            );
+        }
+        let output = runner::run_program(command, None, Error::Transcription).await?;
+
+        serde_json::from_slice(&output.stdout).map_err(|e| {
+            Error::Transcription(format!(
+                "{:?} did not print a valid transcription: {}",
+                self.program, e
+            ))
+        })
+    }
+}
+
+impl Backend for CommandBackend {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("command {:?} {:?}", self.program, self.args)
+    }
+
+    fn transcribe<'a>(&'a self, request: &'a Request<'a>) -> BackendFuture<'a> {
+        Box::pin(self.run(request))
+    }
+}
@@ -0,0 +1,192 @@
+//! Names and jargon the backend keeps getting wrong.
+//!
+//! The terms are sent as the prompt, which biases Whisper towards their
+//! spelling, and whatever still comes back misspelled is replaced in the
+//! words and segments before the subtitles are built. The cached response
+//! stays as the backend returned it, so a changed glossary applies on
+//! [`super::regenerate`].
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Longest prompt sent. Whisper only reads its last 224 tokens, about this
+/// many characters.
+const MAX_PROMPT_CHARS: usize = 800;
+
+/// A term as it should be spelled, and how it gets misheard.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Term {
+    pub term: String,
+    /// Replaced by `term`, matching whole words and ignoring case and
+    /// punctuation. The term itself always is, which fixes its case.
+    #[serde(default)]
+    pub replace: Vec<String>,
+}
+
+/// `global` followed by `project`, where a project term replaces the global
+/// one with the same spelling.
+pub fn merge(global: &[Term], project: &[Term]) -> Vec<Term> {
+    global
+        .iter()
+        .filter(|term| !project.iter().any(|own| own.term.eq_ignore_ascii_case(&term.term)))
+        .chain(project)
+        .cloned()
+        .collect()
+}
+
+/// The prompt sent with transcription requests, `None` without terms. The
+/// last terms are kept when they don't all fit.
+pub fn prompt(terms: &[Term]) -> Option<String> {
+    let mut kept = Vec::new();
+    let mut length = 0;
+    for term in terms.iter().rev() {
+        let term = term.term.trim();
+        if term.is_empty() {
+            continue;
+        }
+        length += term.len() + 2;
+        if length > MAX_PROMPT_CHARS {
+            break;
+        }
+        kept.push(term);
+    }
+    if kept.is_empty() {
+        return None;
+    }
+    kept.reverse();
+    Some(format!("Glossary: {}.", kept.join(", ")))
+}
+
+/// A misspelling, as normalized words, and its replacement.
+struct Pattern {
+    words: Vec<String>,
+    term: String,
+}
+
+impl Pattern {
+    fn new(misspelling: &str, term: &str) -> Option<Self> {
+        let words: Vec<String> = misspelling.split_whitespace().map(normalize).collect();
+        let term = term.trim();
+        if term.is_empty() || words.is_empty() || words.iter().any(String::is_empty) {
+            return None;
+        }
+        Some(Pattern {
+            words,
+            term: term.to_string(),
+        })
+    }
+}
+
+/// Replaces the misspellings in the `words`, `segments` and `text` of a
+/// `verbose_json` transcription. A misspelling spanning several words
+/// becomes a single word from the start of the first to the end of the last.
+pub fn apply(transcription: &mut Value, terms: &[Term]) {
+    // later terms, the project's, win over earlier ones
+    let patterns = terms
+        .iter()
+        .rev()
+        .flat_map(|term| {
+            std::iter::once(&term.term)
+                .chain(&term.replace)
+                .filter_map(|misspelling| Pattern::new(misspelling, &term.term))
+        })
+        .collect();
+    rewrite(transcription, patterns);
+}
+
+/// Like [`apply`] for `(misspelling, replacement)` pairs, e.g. learned
+/// corrections. Unlike glossary terms, a replacement does not also fix the
+/// case of its own occurrences.
+pub fn apply_replacements(transcription: &mut Value, replacements: &[(String, String)]) {
+    let patterns = replacements
+        .iter()
+        .filter_map(|(misspelling, replacement)| Pattern::new(misspelling, replacement))
+        .collect();
+    rewrite(transcription, patterns);
+}
+
+fn rewrite(transcription: &mut Value, mut patterns: Vec<Pattern>) {
+    if patterns.is_empty() {
+        return;
+    }
+    // the longest misspelling wins where several match
+    patterns.sort_by_key(|pattern| std::cmp::Reverse(pattern.words.len()));
+
+    if let Some(words) = transcription["words"].as_array_mut() {
+        let replaced = replace_runs(
+            std::mem::take(words),
+            &patterns,
+            |word| word["word"].as_str().unwrap_or_default(),
+            |run, term| {
+                let first = run[0]["word"].as_str().unwrap_or_default();
+                let last = run[run.len() - 1]["word"].as_str().unwrap_or_default();
+                let mut word = run[0].clone();
+                word["word"] = Value::from(respell(first, last, term));
+                word["end"] = run[run.len() - 1]["end"].clone();
+                word
+            },
+        );
+        *words = replaced;
+    }
+    if let Some(segments) = transcription["segments"].as_array_mut() {
+        for segment in segments {
+            if let Some(text) = segment["text"].as_str() {
+                segment["text"] = Value::from(replace_in_text(text, &patterns));
+            }
+        }
+    }
+    if let Some(text) = transcription["text"].as_str() {
+        transcription["text"] = Value::from(replace_in_text(text, &patterns));
+    }
+}
+
+/// `text` with the misspellings replaced, keeping its leading whitespace.
+fn replace_in_text(text: &str, patterns: &[Pattern]) -> String {
+    let words = text.split_whitespace().map(str::to_string).collect();
+    let replaced = replace_runs(words, patterns, String::as_str, |run, term| {
+        respell(&run[0], &run[run.len() - 1], term)
+    });
+    let indent = &text[..text.len() - text.trim_start().len()];
+    format!("{}{}", indent, replaced.join(" "))
+}
+
+/// Replaces every run of items matching a pattern by the item `join` makes
+/// of the run.
+fn replace_runs<T: Clone>(
+    items: Vec<T>,
+    patterns: &[Pattern],
+    text: impl Fn(&T) -> &str,
+    join: impl Fn(&[T], &str) -> T,
+) -> Vec<T> {
+    let keys: Vec<String> = items.iter().map(|item| normalize(text(item))).collect();
+    let mut replaced = Vec::with_capacity(items.len());
+    let mut i = 0;
+    while i < items.len() {
+        match patterns.iter().find(|pattern| keys[i..].starts_with(&pattern.words)) {
+            Some(pattern) => {
+                let end = i + pattern.words.len();
+                replaced.push(join(&items[i..end], &pattern.term));
+                i = end;
+            }
+            None => {
+                replaced.push(items[i].clone());
+                i += 1;
+            }
+        }
+    }
+    replaced
+}
+
+/// `term` with the punctuation and spacing around the misspelling, which
+/// started at `first` and ended at `last`.
+fn respell(first: &str, last: &str, term: &str) -> String {
+    let leading = first.len() - first.trim_start_matches(|c: char| !c.is_alphanumeric()).len();
+    let trailing = last.trim_end_matches(|c: char| !c.is_alphanumeric()).len();
+    format!("{}{}{}", &first[..leading], term, &last[trailing..])
+}
+
+/// Lowercase, without the punctuation around it.
+pub fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase()
+}
@@ -0,0 +1,249 @@
+//! ISO-639-1 language codes, checked before anything is uploaded, and the
+//! language names `verbose_json` reports detected languages with.
+
+use crate::error::{Error, Result};
+
+/// Every ISO-639-1 code with its English name, lowercase. Where Whisper
+/// knows the language, the name is the one it reports.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("aa", "afar"),
+    ("ab", "abkhazian"),
+    ("ae", "avestan"),
+    ("af", "afrikaans"),
+    ("ak", "akan"),
+    ("am", "amharic"),
+    ("an", "aragonese"),
+    ("ar", "arabic"),
+    ("as", "assamese"),
+    ("av", "avaric"),
+    ("ay", "aymara"),
+    ("az", "azerbaijani"),
+    ("ba", "bashkir"),
+    ("be", "belarusian"),
+    ("bg", "bulgarian"),
+    ("bi", "bislama"),
+    ("bm", "bambara"),
+    ("bn", "bengali"),
+    ("bo", "tibetan"),
+    ("br", "breton"),
+    ("bs", "bosnian"),
+    ("ca", "catalan"),
+    ("ce", "chechen"),
+    ("ch", "chamorro"),
+    ("co", "corsican"),
+    ("cr", "cree"),
+    ("cs", "czech"),
+    ("cu", "church slavic"),
+    ("cv", "chuvash"),
+    ("cy", "welsh"),
+    ("da", "danish"),
+    ("de", "german"),
+    ("dv", "divehi"),
+    ("dz", "dzongkha"),
+    ("ee", "ewe"),
+    ("el", "greek"),
+    ("en", "english"),
+    ("eo", "esperanto"),
+    ("es", "spanish"),
+    ("et", "estonian"),
+    ("eu", "basque"),
+    ("fa", "persian"),
+    ("ff", "fulah"),
+    ("fi", "finnish"),
+    ("fj", "fijian"),
+    ("fo", "faroese"),
+    ("fr", "french"),
+    ("fy", "western frisian"),
+    ("ga", "irish"),
+    ("gd", "scottish gaelic"),
+    ("gl", "galician"),
+    ("gn", "guarani"),
+    ("gu", "gujarati"),
+    ("gv", "manx"),
+    ("ha", "hausa"),
+    ("he", "hebrew"),
+    ("hi", "hindi"),
+    ("ho", "hiri motu"),
+    ("hr", "croatian"),
+    ("ht", "haitian creole"),
+    ("hu", "hungarian"),
+    ("hy", "armenian"),
+    ("hz", "herero"),
+    ("ia", "interlingua"),
+    ("id", "indonesian"),
+    ("ie", "interlingue"),
+    ("ig", "igbo"),
+    ("ii", "sichuan yi"),
+    ("ik", "inupiaq"),
+    ("io", "ido"),
+    ("is", "icelandic"),
+    ("it", "italian"),
+    ("iu", "inuktitut"),
+    ("ja", "japanese"),
+    ("jv", "javanese"),
+    ("ka", "georgian"),
+    ("kg", "kongo"),
+    ("ki", "kikuyu"),
+    ("kj", "kuanyama"),
+    ("kk", "kazakh"),
+    ("kl", "kalaallisut"),
+    ("km", "khmer"),
+    ("kn", "kannada"),
+    ("ko", "korean"),
+    ("kr", "kanuri"),
+    ("ks", "kashmiri"),
+    ("ku", "kurdish"),
+    ("kv", "komi"),
+    ("kw", "cornish"),
+    ("ky", "kyrgyz"),
+    ("la", "latin"),
+    ("lb", "luxembourgish"),
+    ("lg", "ganda"),
+    ("li", "limburgish"),
+    ("ln", "lingala"),
+    ("lo", "lao"),
+    ("lt", "lithuanian"),
+    ("lu", "luba-katanga"),
+    ("lv", "latvian"),
+    ("mg", "malagasy"),
+    ("mh", "marshallese"),
+    ("mi", "maori"),
+    ("mk", "macedonian"),
+    ("ml", "malayalam"),
+    ("mn", "mongolian"),
+    ("mr", "marathi"),
+    ("ms", "malay"),
+    ("mt", "maltese"),
+    ("my", "myanmar"),
+    ("na", "nauru"),
+    ("nb", "norwegian bokmal"),
+    ("nd", "north ndebele"),
+    ("ne", "nepali"),
+    ("ng", "ndonga"),
+    ("nl", "dutch"),
+    ("nn", "nynorsk"),
+    ("no", "norwegian"),
+    ("nr", "south ndebele"),
+    ("nv", "navajo"),
+    ("ny", "chichewa"),
+    ("oc", "occitan"),
+    ("oj", "ojibwa"),
+    ("om", "oromo"),
+    ("or", "oriya"),
+    ("os", "ossetian"),
+    ("pa", "punjabi"),
+    ("pi", "pali"),
+    ("pl", "polish"),
+    ("ps", "pashto"),
+    ("pt", "portuguese"),
+    ("qu", "quechua"),
+    ("rm", "romansh"),
+    ("rn", "rundi"),
+    ("ro", "romanian"),
+    ("ru", "russian"),
+    ("rw", "kinyarwanda"),
+    ("sa", "sanskrit"),
+    ("sc", "sardinian"),
+    ("sd", "sindhi"),
+    ("se", "northern sami"),
+    ("sg", "sango"),
+    ("si", "sinhala"),
+    ("sk", "slovak"),
+    ("sl", "slovenian"),
+    ("sm", "samoan"),
+    ("sn", "shona"),
+    ("so", "somali"),
+    ("sq", "albanian"),
+    ("sr", "serbian"),
+    ("ss", "swati"),
+    ("st", "southern sotho"),
+    ("su", "sundanese"),
+    ("sv", "swedish"),
+    ("sw", "swahili"),
+    ("ta", "tamil"),
+    ("te", "telugu"),
+    ("tg", "tajik"),
+    ("th", "thai"),
+    ("ti", "tigrinya"),
+    ("tk", "turkmen"),
+    ("tl", "tagalog"),
+    ("tn", "tswana"),
+    ("to", "tonga"),
+    ("tr", "turkish"),
+    ("ts", "tsonga"),
+    ("tt", "tatar"),
+    ("tw", "twi"),
+    ("ty", "tahitian"),
+    ("ug", "uyghur"),
+    ("uk", "ukrainian"),
+    ("ur", "urdu"),
+    ("uz", "uzbek"),
+    ("ve", "venda"),
+    ("vi", "vietnamese"),
+    ("vo", "volapuk"),
+    ("wa", "walloon"),
+    ("wo", "wolof"),
+    ("xh", "xhosa"),
+    ("yi", "yiddish"),
+    ("yo", "yoruba"),
+    ("za", "zhuang"),
+    ("zh", "chinese"),
+    ("zu", "zulu"),
+];
+
+/// Other names and codes Whisper reports, for languages it names differently.
+const ALIASES: &[(&str, &str)] = &[
+    ("burmese", "my"),
+    ("cantonese", "zh"),
+    ("castilian", "es"),
+    ("flemish", "nl"),
+    ("haitian", "ht"),
+    ("jw", "jv"),
+    ("letzeburgesch", "lb"),
+    ("mandarin", "zh"),
+    ("moldavian", "ro"),
+    ("moldovan", "ro"),
+    ("panjabi", "pa"),
+    ("pushto", "ps"),
+    ("sinhalese", "si"),
+    ("valencian", "ca"),
+    ("yue", "zh"),
+];
+
+/// The lowercase ISO-639-1 code, or [`Error::InvalidInput`] when `code`
+/// is not one.
+pub fn validate(code: &str) -> Result<String> {
+    let code = code.trim().to_lowercase();
+    if LANGUAGES.iter().any(|(known, _)| *known == code) {
+        Ok(code)
+    } else {
+        Err(Error::InvalidInput(format!(
+            "{:?} is not an ISO-639-1 language code, e.g. \"en\"",
+            code
+        )))
+    }
+}
+
+/// ISO-639-1 code of a language as reported by a backend, either a code or
+/// an English name like "english". `None` when it has no ISO-639-1 code.
+pub fn code_of(reported: &str) -> Option<&'static str> {
+    let reported = reported.trim().to_lowercase();
+    let known = LANGUAGES
+        .iter()
+        .find(|(code, name)| *code == reported || *name == reported)
+        .map(|(code, _)| *code);
+    known.or_else(|| {
+        ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == reported)
+            .map(|(_, code)| *code)
+    })
+}
+
+/// Lowercase English name of an ISO-639-1 code, e.g. "spanish".
+pub fn name_of(code: &str) -> Option<&'static str> {
+    LANGUAGES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, name)| *name)
+}
@@ -0,0 +1,700 @@
+//! Speech to text.
+//!
+//! A [`Backend`] turns an audio file into a transcription shaped like the
+//! OpenAI `verbose_json` response, with `words` and `segments` timestamps,
+//! which is what the subtitle variants are built from. [`BackendConfig`] is
+//! the serializable choice of backend, stored per project in the manifest.
+//!
+//! - [`openai`] talks to the OpenAI API or any server exposing the same
+//!   `/audio/transcriptions` endpoint.
+//! - [`command`] runs an external program that prints the JSON on stdout.
+//! - `whisper` runs a local whisper.cpp model on the CPU, with the `whisper`
+//!   feature.
+//!
+//! Audio over the backend's upload limit is split on silence by [`chunking`]
+//! and the transcriptions of the pieces are stitched back together.
+//!
+//! Responses are kept by [`cache`], [`regenerate`] rebuilds the subtitles
+//! from them and [`retranscribe_range`] redoes a part of the subtitles. A
+//! [`glossary`] of names and jargon is sent as the prompt and fixes their
+//! spelling in the response. [`import_transcript`] builds the subtitles from
+//! existing [`captions`] instead.
+
+pub mod cache;
+pub mod captions;
+pub mod chunking;
+pub mod command;
+pub mod glossary;
+pub mod language;
+pub mod openai;
+#[cfg(feature = "whisper")]
+pub mod whisper;
+
+use crate::corrections;
+use crate::diarization;
+use crate::error::{Error, Result};
+use crate::ffmpeg::Progress;
+use crate::storage::{Project, TranscriptionSettings, Workspace};
+use crate::subtitles;
+use crate::toolchain::{self, Feature};
+use crate::Observer;
+use chunking::Chunk;
+use futures::stream::{FuturesOrdered, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+pub use command::CommandBackend;
+pub use openai::{HttpOptions, OpenAiBackend};
+#[cfg(feature = "whisper")]
+pub use whisper::WhisperBackend;
+
+/// What [`transcribe`] and [`retranscribe_range`] need from ffmpeg: no
+/// encoder, chunks are cut without re-encoding.
+pub const TRANSCRIBE_FEATURES: &[Feature] = &[];
+
+pub type BackendFuture<'a> = Pin<Box<dyn Future<Output = Result<Value>> + Send + 'a>>;
+
+/// What to transcribe.
+#[derive(Clone)]
+pub struct Request<'a> {
+    /// An mp3 file.
+    pub audio: &'a Path,
+    /// ISO-639-1 code, lowercase. `None` lets the backend detect it.
+    pub language: Option<&'a str>,
+    /// Text the backend should expect, see [`glossary::prompt`].
+    pub prompt: Option<&'a str>,
+    /// Told about progress by backends that can measure it.
+    pub observer: &'a dyn Observer,
+}
+
+pub trait Backend: Send + Sync {
+    /// Model name recorded in the project manifest.
+    fn model(&self) -> &str;
+
+    /// Everything that changes the output of the backend for the same audio
+    /// and language, part of the [`cache`] key.
+    fn fingerprint(&self) -> String {
+        self.model().to_string()
+    }
+
+    /// Largest audio file accepted in one request, `None` without a limit.
+    fn max_upload_bytes(&self) -> Option<u64> {
+        None
+    }
+
+    /// Transcribes the request and returns a `verbose_json` shaped value.
+    fn transcribe<'a>(&'a self, request: &'a Request<'a>) -> BackendFuture<'a>;
+
+    /// Translates the speech to English, returning a `verbose_json` shaped
+    /// value with `segments`. `None` when the backend cannot translate, an
+    /// HTTP backend may also fail with a 404 or 405 status.
+    fn translate_to_english<'a>(&'a self, _request: &'a Request<'a>) -> Option<BackendFuture<'a>> {
+        None
+    }
+}
+
+/// How [`transcribe`] runs, besides the backend.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// ISO-639-1 code, detected by the backend when `None`.
+    pub language: Option<String>,
+    /// Audio bigger than this is split on silence and sent in pieces, at
+    /// least [`chunking::MIN_CHUNK_BYTES`]. Defaults to the backend's upload
+    /// limit, if it has one.
+    pub max_chunk_bytes: Option<u64>,
+    /// How many pieces are transcribed at the same time.
+    pub concurrency: usize,
+    /// Reuse the cached response for the same audio and parameters instead
+    /// of calling the backend again.
+    pub reuse_cached: bool,
+    /// Global glossary, the project's own terms are added to it.
+    pub glossary: Vec<glossary::Term>,
+}
+
+impl Default for Options {
+    /// Detects the language.
+    fn default() -> Self {
+        Options {
+            language: None,
+            max_chunk_bytes: None,
+            concurrency: 1,
+            reuse_cached: true,
+            glossary: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    pub fn new(language: &str) -> Self {
+        Options {
+            language: Some(language.to_string()),
+            ..Options::default()
+        }
+    }
+}
+
+/// What [`transcribe`] ended up with.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Outcome {
+    /// ISO-639-1 code, as requested or detected. `None` when the detected
+    /// language has no code.
+    pub language: Option<String>,
+    /// Whether the language was detected rather than requested.
+    pub detected: bool,
+    /// Whether the cached response was used instead of the backend.
+    pub cached: bool,
+}
+
+/// Which backend transcribes a project, as stored in `project.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum BackendConfig {
+    /// The OpenAI API, `whisper-1` unless another model is given.
+    #[serde(rename = "openai")]
+    OpenAi {
+        #[serde(default)]
+        model: Option<String>,
+        /// `timeoutSecs` and `maxRetries`, next to the other fields.
+        #[serde(flatten)]
+        http: HttpOptions,
+    },
+    /// A server with the OpenAI transcription endpoint, e.g. a self-hosted
+    /// faster-whisper server or Groq. The API key is optional.
+    #[serde(rename = "openaiCompatible")]
+    OpenAiCompatible {
+        /// Up to and including the version, e.g. "http://localhost:8000/v1".
+        base_url: String,
+        model: String,
+        /// Sent with every request.
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        /// `timeoutSecs` and `maxRetries`, next to the other fields.
+        #[serde(flatten)]
+        http: HttpOptions,
+    },
+    /// An external program, see [`CommandBackend`].
+    Command {
+        program: PathBuf,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// A whisper.cpp ggml model run in-process, see `WhisperBackend`. Only
+    /// available in builds with the `whisper` feature.
+    Whisper {
+        model_path: PathBuf,
+        /// CPU threads, defaults to the number of cores up to 8.
+        #[serde(default)]
+        threads: Option<u32>,
+    },
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig::OpenAi {
+            model: None,
+            http: HttpOptions::default(),
+        }
+    }
+}
+
+impl BackendConfig {
+    /// Builds the backend, `api_key` is used by the HTTP backends.
+    pub fn build(&self, api_key: Option<&str>) -> Result<Box<dyn Backend>> {
+        let api_key = api_key.filter(|key| !key.is_empty()).map(str::to_string);
+        Ok(match self {
+            BackendConfig::OpenAi { model, http } => {
+                let Some(api_key) = api_key else {
+                    return Err(Error::InvalidInput(
+                        "An OpenAI API key is required to transcribe".to_string(),
+                    ));
+                };
+                Box::new(OpenAiBackend::new(
+                    openai::OPENAI_BASE_URL,
+                    model.as_deref().unwrap_or(openai::MODEL),
+                    Some(api_key),
+                    BTreeMap::new(),
+                    http,
+                )?)
+            }
+            BackendConfig::OpenAiCompatible {
+                base_url,
+                model,
+                headers,
+                http,
+            } => Box::new(OpenAiBackend::new(
+                base_url,
+                model,
+                api_key,
+                headers.clone(),
+                http,
+            )?),
+            BackendConfig::Command { program, args } => {
+                Box::new(CommandBackend::new(program.clone(), args.clone()))
+            }
+            #[cfg(feature = "whisper")]
+            BackendConfig::Whisper {
+                model_path,
+                threads,
+            } => Box::new(WhisperBackend::new(model_path.clone(), *threads)?),
+            #[cfg(not(feature = "whisper"))]
+            BackendConfig::Whisper { .. } => {
+                return Err(Error::Environment(
+                    "This build has no offline transcription, rebuild with the `whisper` feature"
+                        .to_string(),
+                ))
+            }
+        })
+    }
+
+    /// Whose API key the backend needs, see [`openai::provider`]. `None`
+    /// for the local backends.
+    pub fn provider(&self) -> Option<String> {
+        match self {
+            BackendConfig::OpenAi { .. } => Some(openai::OPENAI_PROVIDER.to_string()),
+            BackendConfig::OpenAiCompatible { base_url, .. } => Some(openai::provider(base_url)),
+            BackendConfig::Command { .. } | BackendConfig::Whisper { .. } => None,
+        }
+    }
+
+    /// The backend chosen for a project, `fallback` when it has none.
+    pub fn for_project(workspace: &Workspace, video_id: &str, fallback: BackendConfig) -> Self {
+        Project::load(workspace, video_id)
+            .ok()
+            .and_then(|project| project.transcription)
+            .and_then(|settings| settings.backend)
+            .unwrap_or(fallback)
+    }
+}
+
+/// Transcribes `{id}.mp3` with `backend` and writes every subtitle variant,
+/// replacing the previous ones. The response is cached, and a cached one is
+/// used instead of the backend when allowed by `options`. Without a
+/// language in `options` the backend detects it, and the detected one is
+/// saved with the project. Learned corrections and the glossary are applied
+/// to the subtitles, not to the cached response, and the speakers of a
+/// diarized project are labelled again.
+pub async fn transcribe(
+    workspace: &Workspace,
+    video_id: &str,
+    backend: &dyn Backend,
+    options: &Options,
+    observer: &dyn Observer,
+) -> Result<Outcome> {
+    let requested = options.language.as_deref().map(language::validate).transpose()?;
+    options.max_chunk_bytes.map(chunking::check_max_bytes).transpose()?;
+    toolchain::require(TRANSCRIBE_FEATURES).await?;
+    let audio = workspace.file(video_id, ".mp3")?;
+    if !audio.is_file() {
+        return Err(Error::NotFound(format!("No audio for project {}", video_id)));
+    }
+    let max_chunk_bytes = options.max_chunk_bytes.or(backend.max_upload_bytes());
+    let terms = glossary::merge(&options.glossary, &project_glossary(workspace, video_id));
+    let prompt = glossary::prompt(&terms);
+    let key = cache::key(
+        &audio,
+        &cache::Params {
+            backend: &backend.fingerprint(),
+            language: requested.as_deref().unwrap_or_default(),
+            prompt: prompt.as_deref().unwrap_or_default(),
+            max_chunk_bytes,
+        },
+    )?;
+
+    let cached = if options.reuse_cached {
+        cache::load(workspace, video_id, &key)?
+    } else {
+        None
+    };
+    let from_cache = cached.is_some();
+    let mut json_data = match cached {
+        Some(json_data) => {
+            eprintln!("Using the cached transcription {}", key);
+            json_data
+        }
+        None => {
+            eprintln!("Transcribing audio with {}...", backend.model());
+            let json_data = request(
+                backend,
+                &audio,
+                requested.as_deref(),
+                prompt.as_deref(),
+                max_chunk_bytes,
+                options.concurrency,
+                observer,
+            )
+            .await?;
+            cache::store(workspace, video_id, &key, &json_data)?;
+            json_data
+        }
+    };
+    correct(workspace, video_id, &mut json_data, &terms);
+    subtitles::remove_subtitles(workspace, video_id)?;
+    subtitles::write_variants(workspace, video_id, &json_data)?;
+    diarization::label(workspace, video_id)?;
+
+    let detected = requested.is_none();
+    let language = match requested {
+        Some(language) => Some(language),
+        None => detected_language(&json_data).map(str::to_string),
+    };
+    if detected {
+        eprintln!("Detected language: {}", language.as_deref().unwrap_or("unknown"));
+    }
+    Project::update(workspace, video_id, |project| {
+        let settings = project
+            .transcription
+            .get_or_insert_with(TranscriptionSettings::default);
+        settings.language = language.clone();
+        settings.model = Some(backend.model().to_string());
+        settings.response = Some(key);
+        settings.translations.clear();
+    })?;
+
+    Ok(Outcome {
+        language,
+        detected,
+        cached: from_cache,
+    })
+}
+
+/// Transcribes `start..end` seconds of `{id}.mp3` again and replaces the
+/// cues in that range of every subtitle variant, keeping the other cues and
+/// the edits made to them. The language is the one in `options`, else the
+/// project's. The cached response and the translated tracks are left as
+/// they are, so [`regenerate`] undoes the splice.
+pub async fn retranscribe_range(
+    workspace: &Workspace,
+    video_id: &str,
+    backend: &dyn Backend,
+    start: f64,
+    end: f64,
+    options: &Options,
+    observer: &dyn Observer,
+) -> Result<()> {
+    if !(start >= 0.0 && end > start) {
+        return Err(Error::InvalidInput(format!("Invalid range {}-{}", start, end)));
+    }
+    options.max_chunk_bytes.map(chunking::check_max_bytes).transpose()?;
+    toolchain::require(TRANSCRIBE_FEATURES).await?;
+    let audio = workspace.file(video_id, ".mp3")?;
+    if !audio.is_file() {
+        return Err(Error::NotFound(format!("No audio for project {}", video_id)));
+    }
+    subtitles::check_subtitle_variant(workspace, video_id, "segments")?;
+    let language = match options.language.as_deref() {
+        Some(language) => Some(language::validate(language)?),
+        None => Project::load(workspace, video_id)?
+            .transcription
+            .and_then(|settings| settings.language),
+    };
+    let terms = glossary::merge(&options.glossary, &project_glossary(workspace, video_id));
+    let prompt = glossary::prompt(&terms);
+
+    let chunk = Chunk {
+        start,
+        end,
+        path: audio.with_file_name(format!("{}_range.mp3", video_id)),
+    };
+    observer.output(&chunk.path);
+    eprintln!("Transcribing {}-{} with {}...", start, end, backend.model());
+    let result = match chunking::extract(&audio, start, end, &chunk.path).await {
+        Ok(()) => {
+            request(
+                backend,
+                &chunk.path,
+                language.as_deref(),
+                prompt.as_deref(),
+                options.max_chunk_bytes.or(backend.max_upload_bytes()),
+                options.concurrency,
+                observer,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = std::fs::remove_file(&chunk.path) {
+        eprintln!("Error removing file: {}", e);
+    }
+    let mut json_data = chunking::stitch(vec![(&chunk, result?)]);
+    correct(workspace, video_id, &mut json_data, &terms);
+
+    for (sub_type, vtt) in subtitles::variants(&json_data) {
+        let path = workspace.subtitle_file(video_id, sub_type)?;
+        if !path.is_file() {
+            continue;
+        }
+        // the extract can run a little past the range
+        let cues: Vec<subtitles::Cue> = subtitles::parse_cues(&vtt)
+            .into_iter()
+            .map(|mut cue| {
+                cue.start = cue.start.max(start);
+                cue.end = cue.end.min(end);
+                cue
+            })
+            .filter(|cue| cue.end > cue.start)
+            .collect();
+        let spliced = subtitles::splice_cues(&std::fs::read_to_string(&path)?, start, end, &cues);
+        std::fs::write(&path, spliced)?;
+    }
+    diarization::label(workspace, video_id)
+}
+
+/// Builds every subtitle variant from an existing transcript instead of a
+/// backend: SRT, WebVTT or SBV captions, or a `verbose_json` transcription,
+/// see [`captions`]. The transcript is cached like a response, so
+/// [`regenerate`] rebuilds from it, and the learned corrections, the glossary
+/// and the speakers are applied as by [`transcribe`]. The language is
+/// `language`, else the one a `verbose_json` transcript reports.
+pub fn import_transcript(
+    workspace: &Workspace,
+    video_id: &str,
+    file: &Path,
+    language: Option<&str>,
+    glossary: &[glossary::Term],
+) -> Result<Outcome> {
+    let requested = language.map(language::validate).transpose()?;
+    Project::load(workspace, video_id)?;
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| Error::InvalidInput(format!("Cannot read transcript {:?}: {}", file, e)))?;
+    let format = captions::Format::detect(file, &content)?;
+    let mut json_data = captions::parse(&content, format)?;
+    let key = cache::key(
+        file,
+        &cache::Params {
+            backend: "import",
+            language: requested.as_deref().unwrap_or_default(),
+            prompt: "",
+            max_chunk_bytes: None,
+        },
+    )?;
+    cache::store(workspace, video_id, &key, &json_data)?;
+
+    let terms = glossary::merge(glossary, &project_glossary(workspace, video_id));
+    correct(workspace, video_id, &mut json_data, &terms);
+    subtitles::remove_subtitles(workspace, video_id)?;
+    subtitles::write_variants(workspace, video_id, &json_data)?;
+    diarization::label(workspace, video_id)?;
+
+    let detected = requested.is_none();
+    let language = match requested {
+        Some(language) => Some(language),
+        None => detected_language(&json_data).map(str::to_string),
+    };
+    Project::update(workspace, video_id, |project| {
+        let settings = project
+            .transcription
+            .get_or_insert_with(TranscriptionSettings::default);
+        settings.language = language.clone();
+        settings.model = Some("import".to_string());
+        settings.response = Some(key);
+        settings.translations.clear();
+    })?;
+
+    Ok(Outcome {
+        language,
+        detected,
+        cached: false,
+    })
+}
+
+/// Applies the learned corrections, then the glossary `terms`.
+fn correct(workspace: &Workspace, video_id: &str, json_data: &mut Value, terms: &[glossary::Term]) {
+    glossary::apply_replacements(json_data, &corrections::learned(workspace, video_id));
+    glossary::apply(json_data, terms);
+}
+
+/// Terms of the project's own glossary, none when it has no manifest.
+fn project_glossary(workspace: &Workspace, video_id: &str) -> Vec<glossary::Term> {
+    Project::load(workspace, video_id)
+        .ok()
+        .and_then(|project| project.transcription)
+        .map(|settings| settings.glossary)
+        .unwrap_or_default()
+}
+
+/// ISO-639-1 code of the `language` a transcription reports.
+fn detected_language(json_data: &Value) -> Option<&'static str> {
+    json_data["language"].as_str().and_then(language::code_of)
+}
+
+/// Rebuilds every subtitle variant from the cached response they were made
+/// from, without calling a backend, applying the learned corrections and the
+/// current glossary: `glossary` and the project's terms. Edits to the
+/// subtitles and the translated tracks are lost.
+pub fn regenerate(workspace: &Workspace, video_id: &str, glossary: &[glossary::Term]) -> Result<()> {
+    let mut json_data = cached_response(workspace, video_id)?.ok_or_else(|| {
+        Error::NotFound(format!(
+            "No cached transcription for project {}, transcribe it again first",
+            video_id
+        ))
+    })?;
+    let terms = glossary::merge(glossary, &project_glossary(workspace, video_id));
+    correct(workspace, video_id, &mut json_data, &terms);
+
+    subtitles::remove_subtitles(workspace, video_id)?;
+    subtitles::write_variants(workspace, video_id, &json_data)?;
+    diarization::label(workspace, video_id)?;
+    Project::update(workspace, video_id, |project| {
+        if let Some(settings) = project.transcription.as_mut() {
+            settings.translations.clear();
+        }
+    })?;
+    Ok(())
+}
+
+/// The subtitle variant `sub_type` as built from the cached response, with
+/// the glossary but without the learned corrections, for
+/// [`corrections::learn`] to compare edits with. `None` when the project has
+/// no cached response.
+pub fn generated_variant(
+    workspace: &Workspace,
+    video_id: &str,
+    sub_type: &str,
+    glossary: &[glossary::Term],
+) -> Result<Option<String>> {
+    let Some(mut json_data) = cached_response(workspace, video_id)? else {
+        return Ok(None);
+    };
+    glossary::apply(&mut json_data, &glossary::merge(glossary, &project_glossary(workspace, video_id)));
+    let variant = subtitles::variants(&json_data)
+        .into_iter()
+        .find(|(name, _)| *name == sub_type)
+        .map(|(_, vtt)| vtt);
+    Ok(variant)
+}
+
+/// The response the project's subtitles were last built from.
+fn cached_response(workspace: &Workspace, video_id: &str) -> Result<Option<Value>> {
+    let key = Project::load(workspace, video_id)?
+        .transcription
+        .and_then(|settings| settings.response);
+    match key {
+        Some(key) => cache::load(workspace, video_id, &key),
+        None => Ok(None),
+    }
+}
+
+/// Sends the audio to the backend, in pieces when it is bigger than
+/// `max_chunk_bytes`.
+async fn request(
+    backend: &dyn Backend,
+    audio: &Path,
+    language: Option<&str>,
+    prompt: Option<&str>,
+    max_chunk_bytes: Option<u64>,
+    concurrency: usize,
+    observer: &dyn Observer,
+) -> Result<Value> {
+    let chunks = match max_chunk_bytes {
+        Some(max_bytes) => chunking::split(audio, max_bytes, observer).await?,
+        None => Vec::new(),
+    };
+    if chunks.len() > 1 {
+        let result =
+            transcribe_chunks(backend, &chunks, language, prompt, concurrency, observer).await;
+        chunking::remove(&chunks);
+        result
+    } else {
+        backend
+            .transcribe(&Request {
+                audio,
+                language,
+                prompt,
+                observer,
+            })
+            .await
+            .and_then(check_timestamps)
+    }
+}
+
+/// Transcribes the chunks, `concurrency` at a time, and stitches the results
+/// in order. Progress is reported per finished chunk. Without a language,
+/// the one detected in the first chunk is used for the others.
+async fn transcribe_chunks(
+    backend: &dyn Backend,
+    chunks: &[Chunk],
+    language: Option<&str>,
+    prompt: Option<&str>,
+    concurrency: usize,
+    observer: &dyn Observer,
+) -> Result<Value> {
+    let duration = chunks.last().map_or(0.0, |chunk| chunk.end);
+    let mut parts = Vec::new();
+    let report = |parts: &[(&Chunk, Value)]| {
+        let transcribed: f64 = parts.iter().map(|(chunk, _)| chunk.end - chunk.start).sum();
+        let progress = Progress {
+            out_time: transcribed,
+            duration: Some(duration),
+            percent: Some(transcribed / duration * 100.0),
+            ..Default::default()
+        };
+        observer.progress("transcribe", &progress);
+    };
+
+    let mut waiting = chunks.iter();
+    let mut language = language;
+    if language.is_none() {
+        if let Some(first) = waiting.next() {
+            let (chunk, part) = transcribe_chunk(backend, first, None, prompt).await?;
+            language = detected_language(&part);
+            parts.push((chunk, part));
+            report(&parts);
+        }
+    }
+
+    let mut running = FuturesOrdered::new();
+    for chunk in waiting.by_ref().take(concurrency.max(1)) {
+        running.push_back(transcribe_chunk(backend, chunk, language, prompt));
+    }
+    while let Some(result) = running.next().await {
+        let (chunk, part) = result?;
+        if let Some(next) = waiting.next() {
+            running.push_back(transcribe_chunk(backend, next, language, prompt));
+        }
+        parts.push((chunk, part));
+        report(&parts);
+    }
+
+    Ok(chunking::stitch(parts))
+}
+
+async fn transcribe_chunk<'a>(
+    backend: &'a dyn Backend,
+    chunk: &'a Chunk,
+    language: Option<&'a str>,
+    prompt: Option<&'a str>,
+) -> Result<(&'a Chunk, Value)> {
+    let request = Request {
+        audio: &chunk.path,
+        language,
+        prompt,
+        observer: &(),
+    };
+    let part = backend
+        .transcribe(&request)
+        .await
+        .and_then(check_timestamps)?;
+    Ok((chunk, part))
+}
+
+/// Subtitles are built from word and segment timestamps, a server that
+/// ignored `timestamp_granularities` or returned plain `json` is not usable.
+fn check_timestamps(transcription: Value) -> Result<Value> {
+    let missing: Vec<&str> = ["words", "segments"]
+        .into_iter()
+        .filter(|key| !transcription[*key].is_array())
+        .collect();
+    if missing.is_empty() {
+        return Ok(transcription);
+    }
+    Err(Error::Transcription(format!(
+        "The transcription has no {} timestamps, the backend must return `verbose_json` \
+         with word and segment `timestamp_granularities`",
+        missing.join(" or ")
+    )))
+}
@@ -0,0 +1,403 @@
+//! The OpenAI `/audio/transcriptions` and `/audio/translations` endpoints,
+//! and servers copying them.
+//!
+//! Requests time out, and rate limits, server errors and network failures
+//! are retried with exponential backoff and jitter, waiting as long as the
+//! server asks with `Retry-After`, in seconds or as a date. Failures end up as [`Error::ApiFailed`]
+//! with the reason classified.
+
+use super::{Backend, BackendFuture, Request};
+use crate::error::{ApiFailure, ApiFailureReason, Error, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{multipart, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+/// Provider name of the OpenAI API, see [`provider`].
+pub const OPENAI_PROVIDER: &str = "openai";
+/// Model used with the OpenAI API when none is configured.
+pub const MODEL: &str = "whisper-1";
+/// Upload limit of the OpenAI endpoint, also assumed for compatible servers.
+pub const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+
+/// Wait before the first retry, doubled on every following one.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Longest wait between two attempts, `Retry-After` included.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(120);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How much of an error response is kept in [`ApiFailure::body`].
+const MAX_BODY_CHARS: usize = 2000;
+
+/// Which account an API key of the server at `base_url` belongs to:
+/// [`OPENAI_PROVIDER`] for the OpenAI API, the base URL for the others.
+pub fn provider(base_url: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    if base_url == OPENAI_BASE_URL {
+        OPENAI_PROVIDER.to_string()
+    } else {
+        base_url.to_string()
+    }
+}
+
+/// Timeout and retries of the HTTP backends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpOptions {
+    /// Seconds a request may take, upload included. 0 is refused, there is
+    /// no way to wait forever.
+    pub timeout_secs: u64,
+    /// Retries after the first attempt, for rate limits and transient failures.
+    pub max_retries: u32,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            timeout_secs: 600,
+            max_retries: 4,
+        }
+    }
+}
+
+/// An OpenAI-compatible API: where it is, how to authenticate and how long
+/// to keep trying. Shared by transcription and translation.
+#[derive(Clone)]
+pub struct Client {
+    base_url: String,
+    api_key: Option<String>,
+    headers: HeaderMap,
+    max_retries: u32,
+    client: reqwest::Client,
+}
+
+impl Client {
+    /// `base_url` is everything before the endpoint path, e.g.
+    /// `/audio/transcriptions`. Without an `api_key` no `Authorization`
+    /// header is sent. Fails with [`Error::InvalidInput`] on a header that
+    /// is not valid HTTP.
+    pub fn new(
+        base_url: &str,
+        api_key: Option<String>,
+        headers: BTreeMap<String, String>,
+        http: &HttpOptions,
+    ) -> Result<Self> {
+        if http.timeout_secs == 0 {
+            return Err(Error::InvalidInput(
+                "The request timeout must be at least 1 second".to_string(),
+            ));
+        }
+        let mut header_map = HeaderMap::new();
+        for (name, value) in &headers {
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| Error::InvalidInput(format!("Invalid value of header {:?}: {}", name, e)))?;
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::InvalidInput(format!("Invalid header name {:?}: {}", name, e)))?;
+            header_map.insert(name, value);
+        }
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(Duration::from_secs(http.timeout_secs))
+            .build()
+            .map_err(|e| Error::Environment(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Client {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            headers: header_map,
+            max_retries: http.max_retries,
+            client,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// POSTs to `path` whatever `build` adds to the request, until it
+    /// succeeds, fails for good or runs out of retries. `build` runs again
+    /// for every attempt.
+    pub async fn post_json<F>(&self, path: &str, build: F) -> Result<Value>
+    where
+        F: Fn(RequestBuilder) -> Result<RequestBuilder>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let mut builder = self.client.post(format!("{}{}", self.base_url, path));
+            if let Some(api_key) = &self.api_key {
+                builder = builder.bearer_auth(api_key);
+            }
+            builder = builder.headers(self.headers.clone());
+
+            match send(build(builder)?).await {
+                Ok(value) => return Ok(value),
+                Err(failure) if failure.retryable() && attempts <= self.max_retries => {
+                    let delay = failure
+                        .retry_after
+                        .unwrap_or_else(|| backoff(attempts))
+                        .min(MAX_RETRY_DELAY);
+                    eprintln!(
+                        "Request to {} failed ({}), retrying in {:.1}s",
+                        path,
+                        failure.message,
+                        delay.as_secs_f64()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(failure) => return Err(failure.into_error(attempts)),
+            }
+        }
+    }
+}
+
+/// Which audio endpoint a request goes to.
+#[derive(Clone, Copy)]
+enum Task {
+    Transcribe,
+    /// Speech in any language to English text.
+    Translate,
+}
+
+pub struct OpenAiBackend {
+    client: Client,
+    model: String,
+}
+
+impl OpenAiBackend {
+    /// See [`Client::new`] for the arguments.
+    pub fn new(
+        base_url: &str,
+        model: &str,
+        api_key: Option<String>,
+        headers: BTreeMap<String, String>,
+        http: &HttpOptions,
+    ) -> Result<Self> {
+        Ok(OpenAiBackend {
+            client: Client::new(base_url, api_key, headers, http)?,
+            model: model.to_string(),
+        })
+    }
+
+    /// Sends the mp3 audio, returning the `verbose_json` response. It has
+    /// word and segment timestamps when transcribing, only segments when
+    /// translating.
+    async fn request(&self, request: &Request<'_>, task: Task) -> Result<Value> {
+        let audio = tokio::fs::read(request.audio).await?;
+        let path = match task {
+            Task::Transcribe => "/audio/transcriptions",
+            Task::Translate => "/audio/translations",
+        };
+        self.client
+            .post_json(path, |builder| Ok(builder.multipart(self.form(&audio, request, task)?)))
+            .await
+    }
+
+    fn form(&self, audio: &[u8], request: &Request<'_>, task: Task) -> Result<multipart::Form> {
+        let file_part = multipart::Part::bytes(audio.to_vec())
+            .file_name("file")
+            .mime_str("audio/mp3")
+            .map_err(|e| Error::Transcription(format!("Invalid audio part: {}", e)))?;
+
+        let mut form = multipart::Form::new()
+            .part("file", file_part)
+            .text("response_format", "verbose_json")
+            .text("model", self.model.clone());
+        if let Task::Translate = task {
+            return Ok(form);
+        }
+
+        // without it the API detects the language
+        if let Some(language) = request.language {
+            form = form.text("language", language.to_string());
+        }
+        if let Some(prompt) = request.prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+        for granularity in ["word", "segment"] {
+            form = form.text("timestamp_granularities[]", granularity);
+        }
+        Ok(form)
+    }
+}
+
+impl Backend for OpenAiBackend {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("openai {} {}", self.client.base_url(), self.model)
+    }
+
+    fn max_upload_bytes(&self) -> Option<u64> {
+        Some(MAX_UPLOAD_BYTES)
+    }
+
+    fn transcribe<'a>(&'a self, request: &'a Request<'a>) -> BackendFuture<'a> {
+        Box::pin(self.request(request, Task::Transcribe))
+    }
+
+    fn translate_to_english<'a>(&'a self, request: &'a Request<'a>) -> Option<BackendFuture<'a>> {
+        Some(Box::pin(self.request(request, Task::Translate)))
+    }
+}
+
+/// One attempt, returning the response JSON.
+async fn send(builder: RequestBuilder) -> Result<Value, Attempt> {
+    let res = builder.send().await.map_err(Attempt::from_reqwest)?;
+
+    let status = res.status();
+    let retry_after = retry_after(res.headers());
+    let res = res.text().await.map_err(Attempt::from_reqwest)?;
+    if !status.is_success() {
+        return Err(Attempt::from_status(status, res, retry_after));
+    }
+
+    serde_json::from_str(&res).map_err(|e| {
+        let mut attempt = Attempt::new(
+            ApiFailureReason::BadResponse,
+            format!("Invalid response JSON: {}", e),
+        );
+        attempt.status = Some(status.as_u16());
+        attempt.body = Some(truncate(res));
+        attempt
+    })
+}
+
+/// A failed attempt, before we know whether it is retried.
+struct Attempt {
+    reason: ApiFailureReason,
+    message: String,
+    status: Option<u16>,
+    body: Option<String>,
+    retry_after: Option<Duration>,
+}
+
+impl Attempt {
+    fn new(reason: ApiFailureReason, message: String) -> Self {
+        Attempt {
+            reason,
+            message,
+            status: None,
+            body: None,
+            retry_after: None,
+        }
+    }
+
+    fn from_reqwest(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Attempt::new(
+                ApiFailureReason::Timeout,
+                format!("Request timed out: {}", error),
+            )
+        } else {
+            Attempt::new(
+                ApiFailureReason::Network,
+                format!("Request failed: {}", error),
+            )
+        }
+    }
+
+    fn from_status(status: StatusCode, body: String, retry_after: Option<Duration>) -> Self {
+        let api_message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|json| json["error"]["message"].as_str().map(str::to_string));
+        let lower = body.to_lowercase();
+        let about_audio = ["audio", "file", "format", "decod", "duration"]
+            .iter()
+            .any(|word| lower.contains(word));
+
+        let (reason, summary) = match status.as_u16() {
+            401 | 403 => (ApiFailureReason::Auth, "The API key was refused"),
+            429 if lower.contains("insufficient_quota") || lower.contains("quota") => {
+                (ApiFailureReason::Quota, "The API account is out of credit")
+            }
+            429 => (ApiFailureReason::RateLimited, "Rate limited by the API"),
+            413 => (
+                ApiFailureReason::InvalidAudio,
+                "The audio is too large for the API",
+            ),
+            400 | 415 | 422 if about_audio => (
+                ApiFailureReason::InvalidAudio,
+                "The API could not use the audio",
+            ),
+            408 | 500..=599 => (ApiFailureReason::Server, "The API had a server error"),
+            _ => (ApiFailureReason::Rejected, "The API rejected the request"),
+        };
+        let message = match api_message {
+            Some(api_message) => format!("{} ({}): {}", summary, status, api_message),
+            None => format!("{} ({})", summary, status),
+        };
+
+        Attempt {
+            reason,
+            message,
+            status: Some(status.as_u16()),
+            body: Some(truncate(body)),
+            retry_after,
+        }
+    }
+
+    fn retryable(&self) -> bool {
+        matches!(
+            self.reason,
+            ApiFailureReason::RateLimited
+                | ApiFailureReason::Server
+                | ApiFailureReason::Timeout
+                | ApiFailureReason::Network
+        )
+    }
+
+    fn into_error(self, attempts: u32) -> Error {
+        let message = if attempts > 1 {
+            format!("{} (after {} attempts)", self.message, attempts)
+        } else {
+            self.message
+        };
+        Error::ApiFailed(Box::new(ApiFailure {
+            message,
+            kind: "transcription",
+            reason: self.reason,
+            status: self.status,
+            body: self.body,
+            attempts,
+        }))
+    }
+}
+
+/// `Retry-After` in seconds or as an HTTP date, or OpenAI's `retry-after-ms`.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+    let seconds = |value: &str| Duration::try_from_secs_f64(value.parse().ok()?).ok();
+    if let Some(delay) = header("retry-after-ms").and_then(seconds) {
+        return Some(delay / 1000);
+    }
+    let value = header("retry-after")?;
+    seconds(value).or_else(|| {
+        // a date in the past means right away
+        let date = httpdate::parse_http_date(value).ok()?;
+        Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+    })
+}
+
+/// Exponential backoff with equal jitter: a random wait between half and all
+/// of `BASE_RETRY_DELAY * 2^(attempt - 1)`.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BASE_RETRY_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+    // the sub-second clock is random enough to spread retries apart
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = 0.5 + 0.5 * (nanos as f64 / 1_000_000_000.0);
+    ceiling.mul_f64(jitter)
+}
+
+fn truncate(mut body: String) -> String {
+    if let Some((index, _)) = body.char_indices().nth(MAX_BODY_CHARS) {
+        body.truncate(index);
+    }
+    body
+}
@@ -0,0 +1,220 @@
+//! Offline transcription with whisper.cpp, through `whisper-rs`.
+//!
+//! The audio is decoded to 16 kHz mono samples with ffmpeg, then the ggml
+//! model runs on the CPU in a blocking thread. Token timestamps are merged
+//! into words so the result has the same `words` and `segments` as the API.
+
+use super::{Backend, BackendFuture, Request};
+use crate::error::{Error, Result};
+use crate::ffmpeg::Progress;
+use crate::media;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+/// Sample rate whisper models expect.
+const SAMPLE_RATE: u32 = 16_000;
+/// Threads used when none are configured, at most.
+const MAX_DEFAULT_THREADS: u32 = 8;
+
+pub struct WhisperBackend {
+    model_path: PathBuf,
+    model: String,
+    threads: u32,
+}
+
+impl WhisperBackend {
+    /// `model_path` is a ggml model file, e.g. `ggml-base.bin`.
+    pub fn new(model_path: PathBuf, threads: Option<u32>) -> Result<Self> {
+        if !model_path.is_file() {
+            return Err(Error::InvalidInput(format!(
+                "Whisper model {:?} does not exist",
+                model_path
+            )));
+        }
+        let model = model_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "whisper.cpp".to_string());
+        let threads = threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get() as u32)
+                .unwrap_or(1)
+                .min(MAX_DEFAULT_THREADS)
+        });
+        Ok(WhisperBackend {
+            model_path,
+            model,
+            threads,
+        })
+    }
+
+    /// Cancelling the job drops this future but not the blocking thread,
+    /// whose result is then thrown away.
+    async fn run(&self, request: &Request<'_>) -> Result<Value> {
+        let samples = media::decode_samples(request.audio, SAMPLE_RATE).await?;
+        let duration = samples.len() as f64 / SAMPLE_RATE as f64;
+
+        let (progress_sender, mut progress_receiver) = mpsc::unbounded_channel();
+        let model_path = self.model_path.clone();
+        let threads = self.threads;
+        let language = request.language.map(str::to_string);
+        let prompt = request.prompt.map(str::to_string);
+        let mut task = tokio::task::spawn_blocking(move || {
+            transcribe_samples(
+                &model_path,
+                threads,
+                language.as_deref(),
+                prompt.as_deref(),
+                &samples,
+                progress_sender,
+            )
+        });
+
+        loop {
+            tokio::select! {
+                Some(percent) = progress_receiver.recv() => {
+                    let percent = percent.clamp(0, 100) as f64;
+                    let progress = Progress {
+                        out_time: duration * percent / 100.0,
+                        duration: Some(duration),
+                        percent: Some(percent),
+                        ..Default::default()
+                    };
+                    request.observer.progress("transcribe", &progress);
+                }
+                result = &mut task => {
+                    let mut transcription = result
+                        .map_err(|e| Error::Transcription(format!("Whisper crashed: {}", e)))??;
+                    transcription["duration"] = json!(duration);
+                    return Ok(transcription);
+                }
+            }
+        }
+    }
+}
+
+impl Backend for WhisperBackend {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("whisper {:?}", self.model_path)
+    }
+
+    fn transcribe<'a>(&'a self, request: &'a Request<'a>) -> BackendFuture<'a> {
+        Box::pin(self.run(request))
+    }
+}
+
+fn transcribe_samples(
+    model_path: &std::path::Path,
+    threads: u32,
+    language: Option<&str>,
+    prompt: Option<&str>,
+    samples: &[f32],
+    progress: mpsc::UnboundedSender<i32>,
+) -> Result<Value> {
+    let model = model_path.to_string_lossy();
+    let context = WhisperContext::new_with_params(&model, WhisperContextParameters::default())
+        .map_err(|e| whisper_error("Failed to load the model", e))?;
+    let mut state = context
+        .create_state()
+        .map_err(|e| whisper_error("Failed to create a state", e))?;
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_n_threads(threads as i32);
+    params.set_language(Some(language.unwrap_or("auto")));
+    if let Some(prompt) = prompt {
+        params.set_initial_prompt(prompt);
+    }
+    params.set_token_timestamps(true);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_special(false);
+    params.set_print_timestamps(false);
+    params.set_progress_callback_safe(move |percent: i32| {
+        // the receiver is gone once the job was cancelled
+        let _ = progress.send(percent);
+    });
+
+    state
+        .full(params, samples)
+        .map_err(|e| whisper_error("Transcription failed", e))?;
+    let language = match language {
+        Some(language) => language.to_string(),
+        None => {
+            let id = state
+                .full_lang_id_from_state()
+                .map_err(|e| whisper_error("Failed to read the detected language", e))?;
+            whisper_rs::get_lang_str(id).unwrap_or_default().to_string()
+        }
+    };
+
+    let segment_count = state
+        .full_n_segments()
+        .map_err(|e| whisper_error("Failed to read segments", e))?;
+    let end_of_text = context.token_eot();
+    let mut text = String::new();
+    let mut segments = Vec::new();
+    let mut words = Vec::new();
+
+    for segment in 0..segment_count {
+        let read = |e| whisper_error("Failed to read a segment", e);
+        let segment_text = state.full_get_segment_text_lossy(segment).map_err(read)?;
+        // whisper.cpp times are in centiseconds
+        let start = state.full_get_segment_t0(segment).map_err(read)? as f64 / 100.0;
+        let end = state.full_get_segment_t1(segment).map_err(read)? as f64 / 100.0;
+        text.push_str(&segment_text);
+        segments.push(json!({
+            "id": segment,
+            "start": start,
+            "end": end,
+            "text": segment_text,
+        }));
+
+        // tokens are word pieces, a leading space starts a new word
+        let mut word: Option<(String, f64, f64)> = None;
+        for token in 0..state.full_n_tokens(segment).map_err(read)? {
+            if state.full_get_token_id(segment, token).map_err(read)? >= end_of_text {
+                continue;
+            }
+            let piece = state.full_get_token_text_lossy(segment, token).map_err(read)?;
+            let data = state.full_get_token_data(segment, token).map_err(read)?;
+            let (piece_start, piece_end) = (data.t0 as f64 / 100.0, data.t1 as f64 / 100.0);
+            match &mut word {
+                Some((text, _, end)) if !piece.starts_with(' ') => {
+                    text.push_str(&piece);
+                    *end = piece_end;
+                }
+                _ => {
+                    if let Some(done) = word.take() {
+                        words.push(word_json(done));
+                    }
+                    word = Some((piece.trim_start().to_string(), piece_start, piece_end));
+                }
+            }
+        }
+        if let Some(done) = word {
+            words.push(word_json(done));
+        }
+    }
+
+    Ok(json!({
+        "task": "transcribe",
+        "language": language,
+        "text": text.trim(),
+        "segments": segments,
+        "words": words,
+    }))
+}
+
+fn word_json((word, start, end): (String, f64, f64)) -> Value {
+    json!({ "word": word, "start": start, "end": end })
+}
+
+fn whisper_error(context: &str, error: whisper_rs::WhisperError) -> Error {
+    Error::Transcription(format!("{}: {}", context, error))
+}
@@ -0,0 +1,369 @@
+//! Subtitle tracks in other languages, made from the segments track so every
+//! cue keeps its timing and the edits made to it.
+//!
+//! English comes from the transcription backend's translation endpoint when
+//! it has one, and the translated segments are spread back over the existing
+//! cues. A compatible server answering 404 or 405 there has no such endpoint.
+//! Other languages, and English when the backend cannot translate, go
+//! through an OpenAI-compatible chat completions endpoint, a batch of cues
+//! at a time. Each language is written as its own subtitle variant, see
+//! [`subtitles::translated_variant`], which can be burned like any other.
+
+use crate::error::{Error, Result};
+use crate::ffmpeg::Progress;
+use crate::storage::{Project, TranscriptionSettings, Workspace};
+use crate::subtitles::{self, Cue};
+use crate::toolchain::{self, Feature};
+use crate::transcription::openai::{self, Client, HttpOptions};
+use crate::transcription::{language, Backend, Request};
+use crate::Observer;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Chat model used with the OpenAI API when none is configured.
+pub const CHAT_MODEL: &str = "gpt-4o-mini";
+/// Cues sent in one chat request.
+const BATCH_SIZE: usize = 40;
+/// What [`translate`] needs from ffmpeg: no encoder, the audio is only sent
+/// to the backend as it is.
+pub const TRANSLATE_FEATURES: &[Feature] = &[];
+
+/// The chat completions endpoint cues are translated with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChatConfig {
+    /// Up to and including the version, the OpenAI API when missing.
+    pub base_url: Option<String>,
+    /// Defaults to [`CHAT_MODEL`].
+    pub model: Option<String>,
+    /// Sent with every request.
+    pub headers: BTreeMap<String, String>,
+    /// `timeoutSecs` and `maxRetries`, next to the other fields.
+    #[serde(flatten)]
+    pub http: HttpOptions,
+}
+
+impl ChatConfig {
+    /// Whose API key translating needs, see [`openai::provider`].
+    pub fn provider(&self) -> String {
+        openai::provider(self.base_url.as_deref().unwrap_or(openai::OPENAI_BASE_URL))
+    }
+
+    /// The OpenAI API needs `api_key`, other servers may not.
+    fn build(&self, api_key: Option<&str>) -> Result<Chat> {
+        let api_key = api_key.filter(|key| !key.is_empty()).map(str::to_string);
+        if self.base_url.is_none() && api_key.is_none() {
+            return Err(Error::InvalidInput(
+                "An OpenAI API key is required to translate".to_string(),
+            ));
+        }
+        Ok(Chat {
+            client: Client::new(
+                self.base_url.as_deref().unwrap_or(openai::OPENAI_BASE_URL),
+                api_key,
+                self.headers.clone(),
+                &self.http,
+            )?,
+            model: self.model.clone().unwrap_or_else(|| CHAT_MODEL.to_string()),
+        })
+    }
+}
+
+struct Chat {
+    client: Client,
+    model: String,
+}
+
+impl Chat {
+    /// Translates one batch of cue texts, returning as many translations in
+    /// the same order.
+    async fn translate(&self, texts: &[&str], source: Option<&str>, target: &str) -> Result<Vec<String>> {
+        let source = source.map_or("the language they are in".to_string(), language_name);
+        let instructions = format!(
+            "You translate video subtitles from {} to {}. You get a JSON object \
+             {{\"cues\": [...]}} and answer with a JSON object {{\"translations\": [...]}} \
+             holding one translation per cue, in the same order. Never merge, split or \
+             skip cues, and keep each translation about as long as its cue.",
+            source,
+            language_name(target)
+        );
+        let body = json!({
+            "model": self.model,
+            "temperature": 0,
+            "response_format": {"type": "json_object"},
+            "messages": [
+                {"role": "system", "content": instructions},
+                {"role": "user", "content": json!({"cues": texts}).to_string()},
+            ],
+        });
+        let response = self
+            .client
+            .post_json("/chat/completions", |builder| {
+                Ok(builder.header(CONTENT_TYPE, "application/json").body(body.to_string()))
+            })
+            .await
+            .map_err(translation_error)?;
+
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| Error::Translation("The chat response has no message".to_string()))?;
+        let translations: Value = serde_json::from_str(content).map_err(|e| {
+            Error::Translation(format!("The chat model did not answer with JSON: {}", e))
+        })?;
+        let translations: Vec<String> = translations["translations"]
+            .as_array()
+            .map(|translations| {
+                translations
+                    .iter()
+                    .map(|text| text.as_str().unwrap_or_default().trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if translations.len() != texts.len() {
+            return Err(Error::Translation(format!(
+                "The chat model returned {} translations for {} cues",
+                translations.len(),
+                texts.len()
+            )));
+        }
+        Ok(translations)
+    }
+}
+
+/// Translates the segments track of a project to every language in
+/// `targets`, ISO-639-1 codes, and writes one subtitle track per language.
+/// `backend` translates to English when it can, `chat` is used otherwise.
+/// Returns the variant name of every track.
+pub async fn translate(
+    workspace: &Workspace,
+    video_id: &str,
+    targets: &[String],
+    backend: Option<&dyn Backend>,
+    chat: &ChatConfig,
+    api_key: Option<&str>,
+    observer: &dyn Observer,
+) -> Result<BTreeMap<String, String>> {
+    let targets = targets
+        .iter()
+        .map(|target| language::validate(target))
+        .collect::<Result<Vec<_>>>()?;
+    toolchain::require(TRANSLATE_FEATURES).await?;
+    let source_path = workspace.subtitle_file(video_id, "segments")?;
+    if !source_path.is_file() {
+        return Err(Error::NotFound(format!(
+            "No subtitles to translate for project {}, transcribe it first",
+            video_id
+        )));
+    }
+    let mut cues = subtitles::parse_cues(&std::fs::read_to_string(&source_path)?);
+    // only the words are translated, the speakers are put back after
+    let voices: Vec<Option<String>> = cues
+        .iter()
+        .map(|cue| subtitles::split_voice(&cue.text).map(|(speaker, _)| speaker.to_string()))
+        .collect();
+    for cue in &mut cues {
+        cue.text = subtitles::strip_voice(&cue.text).to_string();
+    }
+    let source = Project::load(workspace, video_id)?
+        .transcription
+        .and_then(|settings| settings.language);
+
+    let mut chat_client = None;
+    let mut tracks = BTreeMap::new();
+    for (done, target) in targets.iter().enumerate() {
+        let texts = if source.as_deref() == Some(target.as_str()) {
+            cues.iter().map(|cue| cue.text.clone()).collect()
+        } else {
+            match english_from_backend(workspace, video_id, target, &cues, backend, observer).await? {
+                Some(texts) => texts,
+                None => {
+                    if chat_client.is_none() {
+                        chat_client = Some(chat.build(api_key)?);
+                    }
+                    let chat_client = chat_client.as_ref().expect("built above");
+                    eprintln!("Translating subtitles to {} with {}...", target, chat_client.model);
+                    translate_cues(chat_client, &cues, source.as_deref(), target, |translated| {
+                        report(observer, &targets, done, translated as f64 / cues.len().max(1) as f64)
+                    })
+                    .await?
+                }
+            }
+        };
+
+        let translated: Vec<Cue> = cues
+            .iter()
+            .zip(texts)
+            .zip(&voices)
+            .map(|((cue, text), voice)| {
+                let text = subtitles::wrap_words(&text, 3);
+                Cue {
+                    text: match voice {
+                        Some(speaker) => subtitles::with_voice(&text, speaker),
+                        None => text,
+                    },
+                    ..cue.clone()
+                }
+            })
+            .collect();
+        let variant = subtitles::translated_variant(target);
+        std::fs::write(
+            workspace.subtitle_file(video_id, &variant)?,
+            subtitles::write_cues(&translated),
+        )?;
+        report(observer, &targets, done, 1.0);
+        tracks.insert(target.clone(), variant);
+    }
+
+    Project::update(workspace, video_id, |project| {
+        let settings = project
+            .transcription
+            .get_or_insert_with(TranscriptionSettings::default);
+        settings.translations.extend(targets.iter().cloned());
+        settings.translations.sort();
+        settings.translations.dedup();
+    })?;
+
+    Ok(tracks)
+}
+
+/// The cue texts in English from the backend's translation of the audio,
+/// `None` when the target is not English or the backend cannot translate
+/// the whole audio in one request, or has no translation endpoint.
+async fn english_from_backend(
+    workspace: &Workspace,
+    video_id: &str,
+    target: &str,
+    cues: &[Cue],
+    backend: Option<&dyn Backend>,
+    observer: &dyn Observer,
+) -> Result<Option<Vec<String>>> {
+    let Some(backend) = backend.filter(|_| target == "en") else {
+        return Ok(None);
+    };
+    let audio = workspace.file(video_id, ".mp3")?;
+    let size = std::fs::metadata(&audio).map(|metadata| metadata.len()).unwrap_or(0);
+    if size == 0 || backend.max_upload_bytes().is_some_and(|max| size > max) {
+        return Ok(None);
+    }
+    let request = Request {
+        audio: &audio,
+        language: None,
+        prompt: None,
+        observer,
+    };
+    let Some(translation) = backend.translate_to_english(&request) else {
+        return Ok(None);
+    };
+
+    eprintln!("Translating audio to English with {}...", backend.model());
+    let json_data = match translation.await {
+        Ok(json_data) => json_data,
+        Err(Error::ApiFailed(failure)) if matches!(failure.status, Some(404 | 405)) => {
+            eprintln!("{} has no translation endpoint, using the chat model", backend.model());
+            return Ok(None);
+        }
+        Err(e) => return Err(translation_error(e)),
+    };
+    let segments = json_data["segments"].as_array().ok_or_else(|| {
+        Error::Translation("The translation has no segment timestamps".to_string())
+    })?;
+    Ok(Some(align(cues, segments)))
+}
+
+/// `e` as a translation error, for the requests shared with transcription.
+fn translation_error(e: Error) -> Error {
+    match e {
+        Error::Transcription(message) => Error::Translation(message),
+        Error::ApiFailed(mut failure) => {
+            failure.kind = "translation";
+            Error::ApiFailed(failure)
+        }
+        e => e,
+    }
+}
+
+/// Spreads the words of every segment over the cues it overlaps, in
+/// proportion to the overlap, so the cues keep their timings. A segment
+/// overlapping no cue goes to the nearest one.
+fn align(cues: &[Cue], segments: &[Value]) -> Vec<String> {
+    let mut texts: Vec<Vec<&str>> = vec![Vec::new(); cues.len()];
+    for segment in segments {
+        let (Some(start), Some(end), Some(text)) = (
+            segment["start"].as_f64(),
+            segment["end"].as_f64(),
+            segment["text"].as_str(),
+        ) else {
+            continue;
+        };
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let overlaps: Vec<(usize, f64)> = cues
+            .iter()
+            .enumerate()
+            .map(|(i, cue)| (i, cue.end.min(end) - cue.start.max(start)))
+            .filter(|(_, overlap)| *overlap > 0.0)
+            .collect();
+
+        if overlaps.is_empty() {
+            let middle = (start + end) / 2.0;
+            let distance = |cue: &Cue| (middle - (cue.start + cue.end) / 2.0).abs();
+            let nearest = (0..cues.len()).min_by(|a, b| distance(&cues[*a]).total_cmp(&distance(&cues[*b])));
+            if let Some(nearest) = nearest {
+                texts[nearest].extend(&words);
+            }
+            continue;
+        }
+
+        let total: f64 = overlaps.iter().map(|(_, overlap)| overlap).sum();
+        let mut covered = 0.0;
+        let mut taken = 0;
+        for (i, overlap) in overlaps {
+            covered += overlap;
+            let until = ((covered / total * words.len() as f64).round() as usize).clamp(taken, words.len());
+            texts[i].extend(&words[taken..until]);
+            taken = until;
+        }
+    }
+    texts.into_iter().map(|words| words.join(" ")).collect()
+}
+
+/// Translates the cue texts in batches of [`BATCH_SIZE`], calling `progress`
+/// with the number of cues done after each.
+async fn translate_cues(
+    chat: &Chat,
+    cues: &[Cue],
+    source: Option<&str>,
+    target: &str,
+    progress: impl Fn(usize),
+) -> Result<Vec<String>> {
+    // one line per cue, the line breaks are put back by word count
+    let texts: Vec<String> = cues.iter().map(|cue| cue.text.replace('\n', " ")).collect();
+    let mut translations = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(BATCH_SIZE) {
+        let batch: Vec<&str> = batch.iter().map(String::as_str).collect();
+        translations.extend(chat.translate(&batch, source, target).await?);
+        progress(translations.len());
+    }
+    Ok(translations)
+}
+
+/// Reports `fraction` of the track at index `done` as "translate" progress
+/// over all `targets`.
+fn report(observer: &dyn Observer, targets: &[String], done: usize, fraction: f64) {
+    let progress = Progress {
+        percent: Some((done as f64 + fraction) / targets.len() as f64 * 100.0),
+        ..Default::default()
+    };
+    observer.progress("translate", &progress);
+}
+
+/// English name of a language for the prompt, e.g. "Spanish".
+fn language_name(code: &str) -> String {
+    let name = language::name_of(code).unwrap_or(code);
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,155 @@
+//! API keys of the transcription and translation providers. The webview can
+//! set and clear them but never reads them back, commands look them up here.
+//!
+//! Keys are stored by provider, see `BackendConfig::provider`, in
+//! `api_keys.bin` in the app config dir, sealed with AES-256-GCM. The
+//! encryption key is made on first use and kept in the app local data dir,
+//! so a synced or backed up config dir carries no usable key. Anyone who
+//! can read all of the user's files can still read them.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use ytb_editor_core::error::{Error, Result};
+
+const KEY_LEN: usize = 32;
+
+/// API keys shared between commands, kept in sync with the file on disk.
+pub struct CredentialStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    keys: Mutex<BTreeMap<String, String>>,
+}
+
+impl CredentialStore {
+    /// Reads the keys sealed in `path` with the encryption key in
+    /// `key_path`. Keys that cannot be read are dropped, with a warning, and
+    /// have to be set again.
+    pub fn open(path: PathBuf, key_path: PathBuf) -> Self {
+        let keys = match load(&path, &key_path) {
+            Ok(keys) => keys,
+            Err(e) => {
+                eprintln!("Ignoring the stored API keys: {}", e);
+                BTreeMap::new()
+            }
+        };
+        CredentialStore {
+            path,
+            key_path,
+            keys: Mutex::new(keys),
+        }
+    }
+
+    /// The key stored for `provider`.
+    pub fn get(&self, provider: &str) -> Option<String> {
+        self.keys.lock().unwrap().get(provider).cloned()
+    }
+
+    /// Providers with a stored key.
+    pub fn providers(&self) -> Vec<String> {
+        self.keys.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn set(&self, provider: &str, api_key: &str) -> Result<()> {
+        let (provider, api_key) = (provider.trim(), api_key.trim());
+        if provider.is_empty() || api_key.is_empty() {
+            return Err(Error::InvalidInput("The provider and the API key are required".to_string()));
+        }
+        let mut keys = self.keys.lock().unwrap();
+        let mut changed = keys.clone();
+        changed.insert(provider.to_string(), api_key.to_string());
+        self.save(&changed)?;
+        *keys = changed;
+        Ok(())
+    }
+
+    /// Forgets the key of `provider`, if there is one.
+    pub fn clear(&self, provider: &str) -> Result<()> {
+        let mut keys = self.keys.lock().unwrap();
+        if !keys.contains_key(provider) {
+            return Ok(());
+        }
+        let mut changed = keys.clone();
+        changed.remove(provider);
+        self.save(&changed)?;
+        *keys = changed;
+        Ok(())
+    }
+
+    fn save(&self, keys: &BTreeMap<String, String>) -> Result<()> {
+        let key = match fs::read(&self.key_path) {
+            Ok(key) => key,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut key = vec![0; KEY_LEN];
+                SystemRandom::new()
+                    .fill(&mut key)
+                    .map_err(|_| Error::Environment("No secure random numbers".to_string()))?;
+                write_private(&self.key_path, &key)?;
+                key
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| Error::Environment("No secure random numbers".to_string()))?;
+
+        let mut sealed = serde_json::to_vec(keys)
+            .map_err(|e| Error::InvalidInput(format!("Failed to serialize API keys: {}", e)))?;
+        cipher(&key, &self.key_path)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)
+            .map_err(|_| Error::Io("Failed to encrypt the API keys".to_string()))?;
+
+        // nonce then ciphertext, written then renamed so a crash never
+        // leaves a truncated file
+        let tmp = self.path.with_extension("tmp");
+        write_private(&tmp, &[&nonce[..], &sealed].concat())?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn load(path: &Path, key_path: &Path) -> Result<BTreeMap<String, String>> {
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    let key = fs::read(key_path)
+        .map_err(|e| Error::Io(format!("Cannot read the encryption key {:?}: {}", key_path, e)))?;
+    let mut sealed = fs::read(path)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(Error::InvalidInput(format!("{:?} is truncated", path)));
+    }
+    let mut ciphertext = sealed.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&sealed)
+        .map_err(|_| Error::InvalidInput(format!("{:?} is truncated", path)))?;
+    let content = cipher(&key, key_path)?
+        .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+        .map_err(|_| {
+            Error::InvalidInput(format!("{:?} was not sealed with {:?}", path, key_path))
+        })?;
+    serde_json::from_slice(content)
+        .map_err(|e| Error::InvalidInput(format!("Invalid API keys in {:?}: {}", path, e)))
+}
+
+fn cipher(key: &[u8], key_path: &Path) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key)
+        .map_err(|_| Error::InvalidInput(format!("Invalid encryption key {:?}", key_path)))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Writes a file only the user can read, on Unix.
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(content)?;
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! Background jobs for long operations (downloads, renders, transcriptions).
+//!
+//! Commands hand their work to [`JobManager::spawn`] and return the job id
+//! right away. The manager tracks each job's state, keeps its result or error,
+//! and can cancel it: cancelling drops the job's future, which kills any
+//! ffmpeg child it was waiting on and aborts in-flight HTTP requests, then
+//! deletes the outputs the job registered.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{oneshot, Semaphore};
+use uuid::Uuid;
+use ytb_editor_core::error::{Error, Result};
+
+/// How many jobs may run at the same time, the rest wait as `queued`.
+const MAX_RUNNING_JOBS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: String,
+    /// What the job does, e.g. "trim" or "transcribe".
+    pub kind: String,
+    pub state: JobState,
+    /// Value returned by the job once it is done.
+    pub result: Option<Value>,
+    pub error: Option<Error>,
+    /// Unix time in milliseconds.
+    pub created_at: u64,
+}
+
+struct Entry {
+    /// Insertion order, jobs are never removed so this stays unique.
+    seq: usize,
+    job: Job,
+    cancel: Option<oneshot::Sender<()>>,
+    outputs: Vec<PathBuf>,
+}
+
+struct Inner {
+    jobs: Mutex<HashMap<String, Entry>>,
+    slots: Semaphore,
+    on_update: Box<dyn Fn(&Job) + Send + Sync>,
+}
+
+#[derive(Clone)]
+pub struct JobManager {
+    inner: Arc<Inner>,
+}
+
+/// Handle given to a running job.
+#[derive(Clone)]
+pub struct JobContext {
+    id: String,
+    manager: JobManager,
+}
+
+impl JobContext {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Registers a file or folder the job writes, deleted if the job is cancelled.
+    pub fn add_output(&self, path: PathBuf) {
+        let mut jobs = self.manager.inner.jobs.lock().unwrap();
+        if let Some(entry) = jobs.get_mut(&self.id) {
+            entry.outputs.push(path);
+        }
+    }
+}
+
+impl JobManager {
+    /// `on_update` is called with a snapshot every time a job changes state.
+    pub fn new(on_update: impl Fn(&Job) + Send + Sync + 'static) -> Self {
+        JobManager {
+            inner: Arc::new(Inner {
+                jobs: Mutex::new(HashMap::new()),
+                slots: Semaphore::new(MAX_RUNNING_JOBS),
+                on_update: Box::new(on_update),
+            }),
+        }
+    }
+
+    /// Queues `task` on the tokio runtime and returns the new job id.
+    pub fn spawn<F, Fut, T>(&self, kind: &str, task: F) -> String
+    where
+        F: FnOnce(JobContext) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Serialize + Send + 'static,
+    {
+        let id = Uuid::new_v4().to_string();
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let job = Job {
+            id: id.clone(),
+            kind: kind.to_string(),
+            state: JobState::Queued,
+            result: None,
+            error: None,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+        };
+
+        {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            let seq = jobs.len();
+            jobs.insert(
+                id.clone(),
+                Entry {
+                    seq,
+                    job: job.clone(),
+                    cancel: Some(cancel_tx),
+                    outputs: Vec::new(),
+                },
+            );
+        }
+        (self.inner.on_update)(&job);
+
+        let future = task(JobContext {
+            id: id.clone(),
+            manager: self.clone(),
+        });
+        let manager = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move { manager.run(job_id, future, cancel_rx).await });
+
+        id
+    }
+
+    async fn run<T: Serialize>(
+        &self,
+        id: String,
+        future: impl Future<Output = Result<T>>,
+        cancel: oneshot::Receiver<()>,
+    ) {
+        let outcome = tokio::select! {
+            outcome = async {
+                let _permit = self.inner.slots.acquire().await.expect("job slots are never closed");
+                self.update(&id, |job| job.state = JobState::Running);
+                future.await
+            } => Some(outcome),
+            _ = cancel => None,
+        };
+
+        match outcome {
+            Some(Ok(value)) => self.update(&id, |job| {
+                job.state = JobState::Done;
+                job.result = serde_json::to_value(value).ok();
+            }),
+            Some(Err(err)) => self.update(&id, |job| {
+                job.state = JobState::Failed;
+                job.error = Some(err);
+            }),
+            None => {
+                self.remove_outputs(&id);
+                self.update(&id, |job| {
+                    job.state = JobState::Cancelled;
+                    job.error = Some(Error::Cancelled("Job was cancelled".to_string()));
+                });
+            }
+        }
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) {
+        let job = {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            let Some(entry) = jobs.get_mut(id) else {
+                return;
+            };
+            change(&mut entry.job);
+            if entry.job.state.is_finished() {
+                entry.cancel = None;
+            }
+            entry.job.clone()
+        };
+        (self.inner.on_update)(&job);
+    }
+
+    fn remove_outputs(&self, id: &str) {
+        let outputs = match self.inner.jobs.lock().unwrap().get_mut(id) {
+            Some(entry) => std::mem::take(&mut entry.outputs),
+            None => return,
+        };
+        for path in outputs {
+            let removed = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            if let Err(e) = removed {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    println!("Error removing {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+
+    /// Cancels a queued or running job. Cancelling a finished job does nothing.
+    pub fn cancel(&self, id: &str) -> Result<()> {
+        let cancel = {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            let entry = jobs
+                .get_mut(id)
+                .ok_or_else(|| Error::NotFound(format!("No job with id {}", id)))?;
+            entry.cancel.take()
+        };
+        if let Some(cancel) = cancel {
+            let _ = cancel.send(());
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Job> {
+        self.inner
+            .jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|entry| entry.job.clone())
+            .ok_or_else(|| Error::NotFound(format!("No job with id {}", id)))
+    }
+
+    /// All jobs, oldest first.
+    pub fn list(&self) -> Vec<Job> {
+        let jobs = self.inner.jobs.lock().unwrap();
+        let mut entries: Vec<&Entry> = jobs.values().collect();
+        entries.sort_by_key(|entry| entry.seq);
+        entries.into_iter().map(|entry| entry.job.clone()).collect()
+    }
+}
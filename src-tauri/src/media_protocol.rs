@@ -0,0 +1,132 @@
+//! `media://` URI scheme serving workspace files to the webview.
+//!
+//! URLs look like `media://localhost/{video_id}/{artifact}` (or
+//! `http://media.localhost/...` on Windows), which is what
+//! `convertFileSrc("{video_id}/{artifact}", "media")` produces on the frontend.
+//! Range requests are answered with `206 Partial Content` so `<video>` can seek.
+//! Responses are built in memory, so a file bigger than `MAX_RANGE_LEN`
+//! requested without a range gets its first range, for the player to ask
+//! for the rest.
+
+use percent_encoding::percent_decode_str;
+use std::io::SeekFrom;
+use std::path::Path;
+use tauri::http::{header, Request, Response, StatusCode};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use ytb_editor_core::error::{Error, Result};
+use ytb_editor_core::storage::Workspace;
+
+pub const SCHEME: &str = "media";
+
+/// Largest body returned for a single range request. Players ask for
+/// `bytes=0-` and keep requesting, so there is no need to send whole files.
+const MAX_RANGE_LEN: u64 = 1024 * 1024;
+
+pub async fn handle(workspace: &Workspace, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    match respond(workspace, request).await {
+        Ok(response) => response,
+        Err(err) => {
+            let status = match err {
+                Error::NotFound(_) => StatusCode::NOT_FOUND,
+                Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Response::builder()
+                .status(status)
+                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .body(err.message().as_bytes().to_vec())
+                .unwrap()
+        }
+    }
+}
+
+async fn respond(workspace: &Workspace, request: &Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+    let path = percent_decode_str(request.uri().path())
+        .decode_utf8()
+        .map_err(|e| Error::InvalidInput(format!("Invalid media path: {}", e)))?;
+    let (video_id, artifact) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .ok_or_else(|| Error::InvalidInput(format!("Invalid media path: {}", path)))?;
+
+    let path = workspace.artifact(video_id, artifact)?;
+    let mut file = tokio::fs::File::open(&path).await?;
+    let len = file.metadata().await?.len();
+
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type(&path))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .or((len > MAX_RANGE_LEN).then_some("bytes=0-"));
+
+    let Some(range) = range else {
+        let mut body = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut body).await?;
+        return Ok(builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, len)
+            .body(body)
+            .unwrap());
+    };
+
+    let Some((start, end)) = parse_range(range, len) else {
+        return Ok(builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+            .body(Vec::new())
+            .unwrap());
+    };
+    let end = end.min(start + MAX_RANGE_LEN - 1);
+
+    let mut body = vec![0; (end - start + 1) as usize];
+    file.seek(SeekFrom::Start(start)).await?;
+    file.read_exact(&mut body).await?;
+
+    Ok(builder
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap())
+}
+
+/// Parses a single `bytes=start-end`, `bytes=start-` or `bytes=-suffix` range
+/// into inclusive offsets. Multi-range requests only get their first range.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let range = range.strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = range.split_once('-')?;
+    if len == 0 {
+        return None;
+    }
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (start, "") => (start.parse().ok()?, len - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(len - 1)),
+    };
+
+    if start > end || start >= len {
+        return None;
+    }
+    Some((start, end))
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mp3") => "audio/mpeg",
+        Some("vtt") => "text/vtt",
+        Some("ass") => "text/x-ssa",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use ytb_editor_core::diarization::DiarizerConfig;
+use ytb_editor_core::error::{Error, Result};
+use ytb_editor_core::toolchain::Overrides;
+use ytb_editor_core::transcription::glossary::Term;
+use ytb_editor_core::transcription::{chunking, BackendConfig};
+use ytb_editor_core::translation::ChatConfig;
+
+/// User settings persisted as `settings.json` in the app config dir.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Where projects are stored. Defaults to `workspace/` in the app data dir.
+    pub workspace_dir: Option<PathBuf>,
+    /// ffmpeg binary to use instead of the bundled one or the one on `PATH`.
+    pub ffmpeg_path: Option<PathBuf>,
+    /// ffprobe binary to use instead of the bundled one or the one on `PATH`.
+    pub ffprobe_path: Option<PathBuf>,
+    /// Backend for projects that don't pick one. Defaults to OpenAI.
+    pub transcription_backend: Option<BackendConfig>,
+    /// Audio bigger than this is transcribed in pieces split on silence, at
+    /// least 1 MB. Defaults to the backend's upload limit.
+    pub transcription_chunk_bytes: Option<u64>,
+    /// Pieces transcribed at the same time, 1 when missing.
+    pub transcription_concurrency: Option<usize>,
+    /// Chat endpoint subtitles are translated with. Defaults to OpenAI.
+    pub translation: Option<ChatConfig>,
+    /// Terms every project is transcribed with.
+    pub glossary: Vec<Term>,
+    /// Finds who speaks when: an external program, e.g. a pyannote script, or
+    /// pyannote ONNX models in builds with the `diarization` feature. No
+    /// model ships with the app, diarization is off when missing.
+    pub diarization: Option<DiarizerConfig>,
+}
+
+impl Settings {
+    /// Reads the settings file, falling back to defaults when it does not exist yet.
+    /// A chunk size the pipeline cannot use is dropped, so the app still starts.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                let mut settings: Settings = serde_json::from_str(&content).map_err(|e| {
+                    Error::InvalidInput(format!("Invalid settings file {:?}: {}", path, e))
+                })?;
+                if let Some(Err(e)) = settings.transcription_chunk_bytes.map(chunking::check_max_bytes) {
+                    eprintln!("Ignoring transcription_chunk_bytes in {:?}: {}", path, e);
+                    settings.transcription_chunk_bytes = None;
+                }
+                Ok(settings)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fails with [`Error::InvalidInput`] on values the pipeline cannot use,
+    /// checked before saving.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(max_bytes) = self.transcription_chunk_bytes {
+            chunking::check_max_bytes(max_bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn toolchain_overrides(&self) -> Overrides {
+        Overrides {
+            ffmpeg: self.ffmpeg_path.clone(),
+            ffprobe: self.ffprobe_path.clone(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidInput(format!("Failed to serialize settings: {}", e)))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Settings shared between commands, kept in sync with the file on disk.
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let settings = Settings::load(&path)?;
+        Ok(SettingsStore {
+            path,
+            settings: Mutex::new(settings),
+        })
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) -> Result<()> {
+        settings.validate()?;
+        settings.save(&self.path)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+}
@@ -3,11 +3,13 @@ import Download from "./pages/Download";
 import Subtitles from "./pages/Subtitles";
 import Editor from "./pages/Editor";
 import NavBar from "./components/NavBar";
+import EnvironmentWarning from "./components/EnvironmentWarning";
 
 export default function App() {
   return (
     <>
       <NavBar />
+      <EnvironmentWarning />
 
       {/* 
       Routes below are matched exclusively -
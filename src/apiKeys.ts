@@ -0,0 +1,42 @@
+import { invoke } from "@tauri-apps/api/core";
+import type { TranscriptionBackend } from "./project";
+
+const OPENAI_BASE_URL = "https://api.openai.com/v1";
+// where keys used to live, before the backend stored them
+const LEGACY_STORAGE_KEY = "openAiApiKey";
+
+// whose API key a backend needs, mirrors `BackendConfig::provider` in
+// src-tauri/core/src/transcription/mod.rs, null for the local backends
+export function apiKeyProvider(backend: TranscriptionBackend) {
+  switch (backend.type) {
+    case "openai":
+      return "openai";
+    case "openaiCompatible": {
+      const baseUrl = backend.baseUrl.replace(/\/+$/, "");
+      return baseUrl === OPENAI_BASE_URL ? "openai" : baseUrl;
+    }
+    default:
+      return null;
+  }
+}
+
+// the keys themselves never come back, only which providers have one
+export function listApiKeys() {
+  return invoke<string[]>("list_api_keys");
+}
+
+export function setApiKey(provider: string, apiKey: string) {
+  return invoke("set_api_key", { provider, apiKey });
+}
+
+export function clearApiKey(provider: string) {
+  return invoke("clear_api_key", { provider });
+}
+
+// hands a key kept in web storage by older versions to the backend
+export async function migrateApiKey() {
+  const apiKey = localStorage.getItem(LEGACY_STORAGE_KEY);
+  if (!apiKey) return;
+  await setApiKey("openai", apiKey);
+  localStorage.removeItem(LEGACY_STORAGE_KEY);
+}
@@ -0,0 +1,60 @@
+import { FC, useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+type BinaryReport = {
+  path: string | null;
+  source: "settings" | "sidecar" | "path" | null;
+  version: string | null;
+  error: string | null;
+};
+
+// mirrors `EnvironmentReport` in src-tauri/core/src/toolchain.rs
+export type EnvironmentReport = {
+  ffmpeg: BinaryReport;
+  ffprobe: BinaryReport;
+  features: { feature: string; description: string; available: boolean }[];
+  ok: boolean;
+};
+
+function problems(report: EnvironmentReport) {
+  const list = [report.ffmpeg.error, report.ffprobe.error].filter(
+    (error): error is string => error !== null
+  );
+  if (!report.ffmpeg.error) {
+    for (const feature of report.features) {
+      if (!feature.available) {
+        list.push(`ffmpeg is missing ${feature.description}`);
+      }
+    }
+  }
+  return list;
+}
+
+const EnvironmentWarning: FC = () => {
+  const [report, setReport] = useState<EnvironmentReport | null>(null);
+
+  async function check() {
+    setReport(await invoke<EnvironmentReport>("check_environment"));
+  }
+
+  useEffect(() => {
+    check().catch(console.error);
+  }, []);
+
+  if (!report || report.ok) {
+    return null;
+  }
+
+  return (
+    <div className="bg-red-900 p-2 text-sm flex flex-col items-center">
+      {problems(report).map((problem) => (
+        <p key={problem}>{problem}</p>
+      ))}
+      <button className="mt-2" onClick={() => check().catch(console.error)}>
+        Check again
+      </button>
+    </div>
+  );
+};
+
+export default EnvironmentWarning;
@@ -5,6 +5,7 @@ import { invoke } from "@tauri-apps/api/core";
 import { FaRegTrashCan } from "react-icons/fa6";
 import { AiOutlineLoading } from "react-icons/ai";
 import { Tooltip } from 'react-tooltip'
+import { showCommandError } from "../pages/Editor/types";
 
 
 const NavBar: FC = () => {
@@ -14,9 +15,14 @@ const NavBar: FC = () => {
 
   async function cleanFiles() {
     setLoading(true);
-    await invoke("clean_files", { videoId });
-    setLoading(false);
-    setLocation("/");
+    try {
+      await invoke("clean_files", { videoId });
+      setLocation("/");
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
   } 
 
   return (
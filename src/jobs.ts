@@ -0,0 +1,58 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import type { CommandError } from "./pages/Editor/types";
+
+export type JobState = "queued" | "running" | "done" | "failed" | "cancelled";
+
+export type Job = {
+  id: string;
+  kind: string;
+  state: JobState;
+  result: unknown;
+  error: CommandError | null;
+  createdAt: number;
+};
+
+export function cancelJob(jobId: string) {
+  return invoke("cancel_job", { jobId });
+}
+
+// Starts a job-returning command and resolves with the job result once it is
+// done. Aborting the signal cancels the job on the backend.
+export async function runJob<T>(
+  command: string,
+  args: Record<string, unknown>,
+  signal?: AbortSignal
+): Promise<T> {
+  // updates can arrive before invoke returns the id, keep them until then
+  const early: Job[] = [];
+  let onUpdate = (job: Job) => {
+    early.push(job);
+  };
+  const unlisten = await listen<Job>("job://updated", (event) =>
+    onUpdate(event.payload)
+  );
+
+  try {
+    const jobId = await invoke<string>(command, args);
+    const onAbort = () => cancelJob(jobId);
+    signal?.addEventListener("abort", onAbort);
+
+    const job = await new Promise<Job>((resolve) => {
+      onUpdate = (job) => {
+        if (job.id === jobId && job.state !== "queued" && job.state !== "running") {
+          resolve(job);
+        }
+      };
+      early.forEach(onUpdate);
+    });
+    signal?.removeEventListener("abort", onAbort);
+
+    if (job.state !== "done") {
+      throw job.error;
+    }
+    return job.result as T;
+  } finally {
+    unlisten();
+  }
+}
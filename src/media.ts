@@ -0,0 +1,56 @@
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
+
+// URL of a workspace file, served by the backend `media://` protocol
+export function mediaUrl(videoId: string, artifact: string) {
+  return convertFileSrc(`${videoId}/${artifact}`, "media");
+}
+
+export type StreamInfo = {
+  index: number;
+  kind: "video" | "audio" | "subtitle" | "data" | "attachment" | "other";
+  codec: string | null;
+  bitRate: number | null;
+  duration: number | null;
+  language: string | null;
+  attachedPicture: boolean;
+  width: number | null;
+  height: number | null;
+  pixelFormat: string | null;
+  fps: number | null;
+  variableFrameRate: boolean;
+  // clockwise, 0, 90, 180 or 270
+  rotation: number;
+  sampleAspectRatio: string | null;
+  displayAspectRatio: string | null;
+  sampleRate: number | null;
+  channels: number | null;
+  channelLayout: string | null;
+};
+
+// mirrors `MediaInfo` in src-tauri/core/src/probe.rs
+export type MediaInfo = {
+  duration: number | null;
+  container: string | null;
+  size: number | null;
+  bitRate: number | null;
+  streams: StreamInfo[];
+};
+
+export function probeMedia(videoId: string, artifact: string) {
+  return invoke<MediaInfo>("probe_media", { videoId, artifact });
+}
+
+// things about a source video the trim step can't fix or should warn about
+export function mediaWarnings(info: MediaInfo) {
+  const warnings: string[] = [];
+  const video = info.streams.find((s) => s.kind === "video" && !s.attachedPicture);
+  if (!video) {
+    warnings.push("This file has no video stream.");
+  } else if (video.variableFrameRate) {
+    warnings.push("This video has a variable frame rate, cuts may be off by a frame.");
+  }
+  if (!info.streams.some((s) => s.kind === "audio")) {
+    warnings.push("This video has no audio track, it can't be trimmed or transcribed.");
+  }
+  return warnings;
+}
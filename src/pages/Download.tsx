@@ -1,9 +1,11 @@
-import { useState } from "react";
-import { invoke } from "@tauri-apps/api/core";
+import { useEffect, useState } from "react";
+import { runJob } from "../jobs";
+import { listProjects, Project } from "../project";
 import { useStore } from "../store";
 import { useLocation } from "wouter";
 import { open } from "@tauri-apps/plugin-dialog";
 import { AiOutlineLoading } from "react-icons/ai";
+import { showCommandError } from "./Editor/types";
 
 function DropZone() {
   const [_, setLocation] = useLocation();
@@ -22,9 +24,16 @@ function DropZone() {
     });
     if (selected) {
       setLoading(true);
-      const videoId = await invoke<string>("copy_file", {
-        filepath: selected.path,
-      });
+      let videoId: string;
+      try {
+        videoId = await runJob<string>("copy_file", {
+          filepath: selected.path,
+        });
+      } catch (error) {
+        showCommandError(error);
+        setLoading(false);
+        return;
+      }
       setVideoId(videoId);
       setTimeout(() => {
         setLoading(false);
@@ -50,6 +59,41 @@ function DropZone() {
   );
 }
 
+function RecentProjects() {
+  const [_, setLocation] = useLocation();
+  const { setVideoId } = useStore();
+  const [projects, setProjects] = useState<Project[]>([]);
+
+  useEffect(() => {
+    listProjects().then(setProjects).catch(showCommandError);
+  }, []);
+
+  function reopen(project: Project) {
+    setVideoId(project.id);
+    // trimmed projects continue on the subtitles page
+    setLocation(project.timings.length > 0 ? "/subtitles" : "/trim");
+  }
+
+  if (projects.length === 0) {
+    return null;
+  }
+
+  return (
+    <div className="flex flex-col items-center space-y-2 m-4">
+      <p className="font-bold">Recent projects</p>
+      {projects.slice(0, 5).map((project) => (
+        <div
+          key={project.id}
+          onClick={() => reopen(project)}
+          className="p-2 border border-gray-300 rounded-md w-80 cursor-pointer truncate"
+        >
+          {project.title ?? project.id}
+        </div>
+      ))}
+    </div>
+  );
+}
+
 function DownloadPage() {
   const { setVideoId } = useStore();
   const [_, setLocation] = useLocation();
@@ -58,10 +102,15 @@ function DownloadPage() {
 
   async function download() {
     setLoading(true);
-    const path = await invoke<string>("download_youtube_video", { url });
-    setVideoId(path);
-    setLoading(false);
-    setLocation("/trim");
+    try {
+      const path = await runJob<string>("download_youtube_video", { url });
+      setVideoId(path);
+      setLocation("/trim");
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
   }
 
   return (
@@ -88,6 +137,7 @@ function DownloadPage() {
         </button>
       </form>
       {loading && <p>Downloading...</p>}
+      <RecentProjects />
     </div>
   );
 }
@@ -1,6 +1,7 @@
 import { useCallback, useRef, useMemo } from "react";
 import { WaveSurfer, WaveForm } from "wavesurfer-react";
 import TimelinePlugin from "wavesurfer.js/dist/plugins/timeline.js";
+import { mediaUrl } from "../../media";
 
 interface AudioWaveProps {
   videoId: string;
@@ -29,7 +30,7 @@ function AudioWave({ videoId }: AudioWaveProps) {
     wavesurferRef.current = waveSurfer;
 
     if (wavesurferRef.current) {
-      wavesurferRef.current.load(`/${videoId}_full.mp3`);
+      wavesurferRef.current.load(mediaUrl(videoId, `${videoId}_full.mp3`));
 
       if (window) {
         // @ts-ignore
@@ -1,38 +1,63 @@
-import { useEffect, useState } from "react";
+import { useEffect, useRef, useState } from "react";
 import { useStore } from "../../store";
-import { invoke } from "@tauri-apps/api/core";
+import { runJob } from "../../jobs";
+import { showCommandError } from "./types";
 import type { Timings, Dimensions } from "./types";
 
 import Editor from "./Editor";
 import "./Editor.css";
 import { useLocation } from "wouter";
+import { mediaUrl, mediaWarnings, probeMedia } from "../../media";
 
 function VideoEditor() {
   const { videoId } = useStore();
   const [loading, setLoading] = useState(false);
+  const [warnings, setWarnings] = useState<string[]>([]);
   const [_, setLocation] = useLocation();
+  // cancels the running trim when leaving the page
+  const abortRef = useRef<AbortController>();
 
   useEffect(() => {
     if (!videoId) {
       setLocation("/");
     }
+    const controller = new AbortController();
+    abortRef.current = controller;
+    probeMedia(videoId, `${videoId}.mp4`)
+      .then((info) => setWarnings(mediaWarnings(info)))
+      .catch(console.error);
+    return () => controller.abort();
   }, []);
 
   async function trimVideo(timings: Timings[], dimensions?: Dimensions) {
     setLoading(true);
-    await invoke("trim_video", {
-      videoId,
-      timings,
-      dimensions,
-    });
-    setLoading(false);
-    setLocation("/subtitles");
+    try {
+      await runJob(
+        "trim_video",
+        {
+          videoId,
+          timings,
+          dimensions,
+        },
+        abortRef.current?.signal
+      );
+      setLocation("/subtitles");
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
   }
 
   return (
     <div>
+      {warnings.map((warning) => (
+        <p key={warning} className="bg-yellow-900 p-2 text-sm text-center">
+          {warning}
+        </p>
+      ))}
       <Editor
-        videoUrl={`./${videoId}.mp4`}
+        videoUrl={mediaUrl(videoId, `${videoId}.mp4`)}
         trimVideo={trimVideo}
         loading={loading}
         videoId={videoId}
@@ -1,4 +1,64 @@
-export type SubtitleStyle = "words" | "segments" | "3words" | "4words" | "5words" | "6words" | "none";
+export type CommandError = {
+  kind:
+    | "download"
+    | "ffmpeg"
+    | "environment"
+    | "transcription"
+    | "diarization"
+    | "translation"
+    | "io"
+    | "invalid_input"
+    | "not_found"
+    | "cancelled";
+  message: string;
+  details?: ProcessFailure | ApiFailure;
+};
+
+// set when ffmpeg/ffprobe, a transcription program or a diarizer exited with
+// an error
+export type ProcessFailure = {
+  command: string;
+  exitCode: number | null;
+  stderrTail: string[];
+};
+
+// set when a transcription or translation request failed, after any retries
+export type ApiFailure = {
+  reason:
+    | "auth"
+    | "quota"
+    | "rateLimited"
+    | "invalidAudio"
+    | "rejected"
+    | "server"
+    | "timeout"
+    | "network"
+    | "badResponse";
+  status: number | null;
+  body: string | null;
+  attempts: number;
+};
+
+export function showCommandError(error: unknown) {
+  const { message, details } = error as CommandError;
+  if (details && "stderrTail" in details) {
+    console.error(details.command, details.stderrTail.join("\n"));
+  } else if (details) {
+    console.error(details.reason, details.status, details.body);
+  }
+  alert(message ?? String(error));
+}
+
+export type SubtitleStyle =
+  | "words"
+  | "segments"
+  | "3words"
+  | "4words"
+  | "5words"
+  | "6words"
+  // a track made by translate_subtitles, e.g. "translated_es"
+  | `translated_${string}`
+  | "none";
 
 export type Timings = {
   start: number;
@@ -58,18 +118,53 @@ export interface SubtitleOptionsProps {
   type: SubtitleStyle;
   setType: (type: SubtitleStyle) => void;
   subtitlesExist: boolean;
+  // languages with a translated track
+  translations: string[];
+}
+
+export interface GlossaryEditorProps {
+  videoId: string;
+}
+
+export interface SpeakersEditorProps {
+  videoId: string;
+  subtitlesExist: boolean;
+  // reloads the subtitles after their cues were labelled
+  refreshSubtitles: () => void;
+}
+
+export interface TranslateSubtitlesProps {
+  translateSubtitles: (language: string) => void;
+  translating: boolean;
+  subtitlesExist: boolean;
+}
+
+export interface RetranscribeRangeProps {
+  retranscribeRange: (start: number, end: number) => void;
+  loading: boolean;
+  subtitlesExist: boolean;
+  videoRef: React.RefObject<HTMLVideoElement>;
 }
 
 export interface GenerateSubtitlesButtonProps {
   createSubtitles: () => void;
+  // rebuilds the variants from the cached transcription
+  regenerateSubtitles: () => void;
+  // SRT, VTT, SBV or verbose_json instead of transcribing
+  importTranscript: () => void;
   loading: boolean;
-  apiKey: string | null;
+  hasApiKey: boolean;
+  // false when the project's backend works without a key
+  keyRequired: boolean;
   subtitlesExist: boolean;
 }
 
 export interface ApiKeyComponentProps {
-  apiKey: string | null;
-  setApiKey: (apiKey: string) => void;
+  // see `apiKeyProvider`
+  provider: string;
+  stored: boolean;
+  // called once the key was saved or forgotten
+  onChange: () => void;
 }
 
 export interface VttTextAreaProps {
@@ -86,6 +181,14 @@ export interface CueVtt {
   fontSize: string;
 }
 
+// result of the transcribe_audio job
+export type TranscriptionOutcome = {
+  // ISO-639-1 code, null when the detected language has none
+  language: string | null;
+  detected: boolean;
+  cached: boolean;
+};
+
 export interface LanguageSelectProps {
   selectedLanguage: string;
   setSelectedLanguage: (language: string) => void;
@@ -1,17 +1,58 @@
 import { useState, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { open } from "@tauri-apps/plugin-dialog";
 import { useStore } from "../../store";
 import { useLocation } from "wouter";
 import VttTextArea from "./VttTextArea";
+import { mediaUrl } from "../../media";
+import { runJob } from "../../jobs";
+import {
+  apiKeyProvider,
+  clearApiKey,
+  listApiKeys,
+  migrateApiKey,
+  setApiKey,
+} from "../../apiKeys";
+import {
+  GlossaryTerm,
+  loadProject,
+  saveGlossary,
+  transcriptionBackend,
+} from "../../project";
 import {
   DownloadButtonProps,
   SubtitleOptionsProps,
   GenerateSubtitlesButtonProps,
   ApiKeyComponentProps,
+  GlossaryEditorProps,
   LanguageSelectProps,
+  RetranscribeRangeProps,
+  SpeakersEditorProps,
   SubtitleStyle,
+  TranslateSubtitlesProps,
+  TranscriptionOutcome,
+  showCommandError,
 } from "../Editor/types";
 
+const LANGUAGES: [string, string][] = [
+  ["en", "English"],
+  ["pt", "Portuguese"],
+  ["es", "Spanish"],
+  ["fr", "French"],
+  ["de", "German"],
+  ["it", "Italian"],
+  ["ja", "Japanese"],
+  ["ko", "Korean"],
+  ["zh", "Chinese"],
+  ["ru", "Russian"],
+  ["ar", "Arabic"],
+  ["hi", "Hindi"],
+];
+
+function translatedVariant(language: string): SubtitleStyle {
+  return `translated_${language}`;
+}
+
 function LanguageSelect({
   selectedLanguage,
   setSelectedLanguage,
@@ -25,54 +66,266 @@ function LanguageSelect({
           value={selectedLanguage}
           onChange={(e) => setSelectedLanguage(e.target.value)}
         >
-          <option value="en">English</option>
-          <option value="pt">Portuguese</option>
-          <option value="es">Spanish</option>
-          <option value="fr">French</option>
-          <option value="de">German</option>
-          <option value="it">Italian</option>
-          <option value="ja">Japanese</option>
-          <option value="ko">Korean</option>
-          <option value="zh">Chinese</option>
-          <option value="ru">Russian</option>
-          <option value="ar">Arabic</option>
-          <option value="hi">Hindi</option>
+          <option value="">Detect automatically</option>
+          {LANGUAGES.map(([code, name]) => (
+            <option key={code} value={code}>
+              {name}
+            </option>
+          ))}
         </select>
       </div>
     </div>
   );
 }
 
-function ApiKeyComponent({ apiKey, setApiKey }: ApiKeyComponentProps) {
+// one term per line, misspellings after a colon: "OpenAI: open eye, open ai"
+function parseGlossary(text: string): GlossaryTerm[] {
+  return text
+    .split("\n")
+    .map((line) => {
+      const [term, replace = ""] = line.split(":");
+      return {
+        term: term.trim(),
+        replace: replace
+          .split(",")
+          .map((misspelling) => misspelling.trim())
+          .filter(Boolean),
+      };
+    })
+    .filter((entry) => entry.term);
+}
+
+function formatGlossary(glossary: GlossaryTerm[]) {
+  return glossary
+    .map(({ term, replace }) =>
+      replace?.length ? `${term}: ${replace.join(", ")}` : term
+    )
+    .join("\n");
+}
+
+function GlossaryEditor({ videoId }: GlossaryEditorProps) {
+  const [text, setText] = useState("");
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    loadProject(videoId)
+      .then((project) =>
+        setText(formatGlossary(project.transcription?.glossary ?? []))
+      )
+      .catch(console.error);
+  }, [videoId]);
+
+  async function save() {
+    setSaving(true);
+    try {
+      await saveGlossary(videoId, parseGlossary(text));
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setSaving(false);
+    }
+  }
+
   return (
-    <div className="flex flex-col items-center justify-center space-y-4 m-4">
-      <label htmlFor="greet-input">Enter your OpenAI key</label>
+    <div className="flex flex-col items-center justify-center space-y-2 m-4">
+      <label htmlFor="glossary-input">Glossary</label>
       <p className="text-sm text-gray-500">
-        You can find your OpenAI key in the{" "}
-        <a
-          href="https://platform.openai.com/account/api-keys"
-          className="text-blue-500"
-          target="_blank"
-          rel="noreferrer"
-        >
-          OpenAI dashboard
-        </a>
+        One name or term per line, followed by how it gets misheard:
+        "OpenAI: open eye, open ai"
       </p>
+      <textarea
+        id="glossary-input"
+        className="p-2 border border-gray-300 rounded-md w-80 h-24"
+        value={text}
+        onChange={(e) => setText(e.currentTarget.value)}
+      />
+      <button
+        className="bg-blue-900 text-white p-2 rounded-md w-80"
+        onClick={save}
+        disabled={saving}
+      >
+        {saving ? "SAVING..." : "SAVE GLOSSARY"}
+      </button>
+    </div>
+  );
+}
+
+// who speaks when, found by the diarizer from the settings or imported
+function SpeakersEditor({
+  videoId,
+  subtitlesExist,
+  refreshSubtitles,
+}: SpeakersEditorProps) {
+  const [speakers, setSpeakers] = useState<Record<string, string>>({});
+  const [busy, setBusy] = useState(false);
+
+  useEffect(() => {
+    loadProject(videoId)
+      .then((project) => setSpeakers(project.speakers ?? {}))
+      .catch(console.error);
+  }, [videoId]);
+
+  async function update(task: () => Promise<Record<string, string> | null>) {
+    setBusy(true);
+    try {
+      const names = await task();
+      if (names) {
+        setSpeakers(names);
+        refreshSubtitles();
+      }
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setBusy(false);
+    }
+  }
+
+  const diarize = () =>
+    update(() =>
+      runJob<Record<string, string>>("diarize_speakers", { videoId })
+    );
+
+  const importFile = () =>
+    update(async () => {
+      const selected = await open({
+        multiple: false,
+        filters: [{ name: "Speaker turns", extensions: ["rttm", "json"] }],
+      });
+      if (!selected) return null;
+      return invoke<Record<string, string>>("import_speakers", {
+        videoId,
+        path: selected.path,
+      });
+    });
+
+  const rename = () =>
+    update(() =>
+      invoke<Record<string, string>>("rename_speakers", {
+        videoId,
+        names: speakers,
+      })
+    );
+
+  if (!subtitlesExist) return null;
+  return (
+    <div className="flex flex-col items-center justify-center space-y-2 m-4">
+      <label>Speakers</label>
+      <div className="flex space-x-2">
+        <button
+          className="bg-blue-900 text-white p-2 rounded-md"
+          onClick={diarize}
+          disabled={busy}
+        >
+          FIND SPEAKERS
+        </button>
+        <button
+          className="bg-blue-900 text-white p-2 rounded-md"
+          onClick={importFile}
+          disabled={busy}
+        >
+          IMPORT RTTM / JSON
+        </button>
+      </div>
+      {Object.entries(speakers).map(([label, name]) => (
+        <div key={label} className="flex items-center space-x-2">
+          <span className="text-sm text-gray-500 w-32 truncate">{label}</span>
+          <input
+            className="p-2 border border-gray-300 rounded-md w-48"
+            value={name}
+            onChange={(e) =>
+              setSpeakers({ ...speakers, [label]: e.currentTarget.value })
+            }
+          />
+        </div>
+      ))}
+      {Object.keys(speakers).length > 0 && (
+        <button
+          className="bg-blue-900 text-white p-2 rounded-md w-80"
+          onClick={rename}
+          disabled={busy}
+        >
+          {busy ? "SAVING..." : "RENAME SPEAKERS"}
+        </button>
+      )}
+    </div>
+  );
+}
+
+// the key is kept by the backend, the webview only sends it once
+function ApiKeyComponent({ provider, stored, onChange }: ApiKeyComponentProps) {
+  const [apiKey, setApiKeyText] = useState("");
+  const [saving, setSaving] = useState(false);
+  const name = provider === "openai" ? "OpenAI" : provider;
+
+  async function update(task: () => Promise<unknown>) {
+    setSaving(true);
+    try {
+      await task();
+      setApiKeyText("");
+      onChange();
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setSaving(false);
+    }
+  }
+
+  if (stored) {
+    return (
+      <div className="flex flex-col items-center justify-center space-y-2 m-4">
+        <p className="text-green-500">API key saved for {name}</p>
+        <button
+          className="bg-blue-900 text-white p-2 rounded-md w-80"
+          onClick={() => update(() => clearApiKey(provider))}
+          disabled={saving}
+        >
+          FORGET API KEY
+        </button>
+      </div>
+    );
+  }
+  return (
+    <div className="flex flex-col items-center justify-center space-y-4 m-4">
+      <label htmlFor="greet-input">Enter your {name} key</label>
+      {provider === "openai" && (
+        <p className="text-sm text-gray-500">
+          You can find your OpenAI key in the{" "}
+          <a
+            href="https://platform.openai.com/account/api-keys"
+            className="text-blue-500"
+            target="_blank"
+            rel="noreferrer"
+          >
+            OpenAI dashboard
+          </a>
+        </p>
+      )}
       <input
         id="greet-input"
+        type="password"
         className="p-2 border border-gray-300 rounded-md w-80"
-        value={apiKey || ""}
-        onChange={(e) => setApiKey(e.currentTarget.value)}
-        placeholder="Enter your OpenAI key..."
+        value={apiKey}
+        onChange={(e) => setApiKeyText(e.currentTarget.value)}
+        placeholder={`Enter your ${name} key...`}
       />
+      <button
+        className="bg-blue-900 text-white p-2 rounded-md w-80"
+        onClick={() => update(() => setApiKey(provider, apiKey))}
+        disabled={saving || !apiKey.trim()}
+      >
+        {saving ? "SAVING..." : "SAVE API KEY"}
+      </button>
     </div>
   );
 }
 
 function GenerateSubtitlesButton({
   createSubtitles,
+  regenerateSubtitles,
+  importTranscript,
   loading,
-  apiKey,
+  hasApiKey,
+  keyRequired,
   subtitlesExist,
 }: GenerateSubtitlesButtonProps) {
   return (
@@ -80,25 +333,126 @@ function GenerateSubtitlesButton({
       <button
         className="bg-blue-500 text-white p-2 rounded-md w-80"
         onClick={createSubtitles}
-        disabled={loading || !apiKey}
+        disabled={loading || (keyRequired && !hasApiKey)}
       >
         {loading
           ? "LOADING..."
-          : apiKey
+          : hasApiKey || !keyRequired
           ? "GENERATE SUBTITLES"
           : "ENTER API KEY"}
       </button>
+      <button
+        className="bg-blue-900 text-white p-2 rounded-md w-80 mt-2"
+        onClick={importTranscript}
+        disabled={loading}
+      >
+        IMPORT CAPTIONS
+      </button>
       {subtitlesExist && (
-        <p className="text-green-500 mt-2">Subtitles exist for this video</p>
+        <>
+          <p className="text-green-500 mt-2">Subtitles exist for this video</p>
+          <button
+            className="bg-blue-900 text-white p-2 rounded-md w-80 mt-2"
+            onClick={regenerateSubtitles}
+            disabled={loading}
+          >
+            REBUILD FROM LAST TRANSCRIPTION
+          </button>
+        </>
       )}
     </div>
   );
 }
 
+// transcribes a garbled part again without touching the cues around it
+function RetranscribeRange({
+  retranscribeRange,
+  loading,
+  subtitlesExist,
+  videoRef,
+}: RetranscribeRangeProps) {
+  const [start, setStart] = useState(0);
+  const [end, setEnd] = useState(0);
+  if (!subtitlesExist) return null;
+
+  const now = () => Number(videoRef.current?.currentTime.toFixed(2) ?? 0);
+  return (
+    <div className="flex justify-center items-center space-x-2 py-4">
+      <label htmlFor="range-start">Transcribe again from</label>
+      <input
+        id="range-start"
+        type="number"
+        min={0}
+        step={0.1}
+        className="bg-gray-100 p-2 rounded-md w-24"
+        value={start}
+        onChange={(e) => setStart(Number(e.target.value))}
+      />
+      <button className="text-blue-500" onClick={() => setStart(now())}>
+        now
+      </button>
+      <label htmlFor="range-end">to</label>
+      <input
+        id="range-end"
+        type="number"
+        min={0}
+        step={0.1}
+        className="bg-gray-100 p-2 rounded-md w-24"
+        value={end}
+        onChange={(e) => setEnd(Number(e.target.value))}
+      />
+      <button className="text-blue-500" onClick={() => setEnd(now())}>
+        now
+      </button>
+      <button
+        className="bg-blue-900 text-white p-2 rounded-md"
+        onClick={() => retranscribeRange(start, end)}
+        disabled={loading || end <= start}
+      >
+        {loading ? "LOADING..." : "TRANSCRIBE RANGE"}
+      </button>
+    </div>
+  );
+}
+
+function TranslateSubtitles({
+  translateSubtitles,
+  translating,
+  subtitlesExist,
+}: TranslateSubtitlesProps) {
+  const [target, setTarget] = useState("en");
+  if (!subtitlesExist) return null;
+  return (
+    <div className="flex justify-center items-center space-x-4 py-4">
+      <label htmlFor="translate-select">Translate subtitles to</label>
+      <select
+        id="translate-select"
+        className="bg-gray-100 p-2 rounded-md"
+        value={target}
+        onChange={(e) => setTarget(e.target.value)}
+      >
+        {LANGUAGES.map(([code, name]) => (
+          <option key={code} value={code}>
+            {name}
+          </option>
+        ))}
+      </select>
+      <button
+        className="bg-blue-900 text-white p-2 rounded-md"
+        onClick={() => translateSubtitles(target)}
+        disabled={translating}
+      >
+        {translating ? "TRANSLATING..." : "TRANSLATE"}
+      </button>
+    </div>
+  );
+}
+
 function SubtitleOptions({
   type,
   setType,
   subtitlesExist,
+  translations,
 }: SubtitleOptionsProps) {
   if (!subtitlesExist) return null;
   console.log(type);
@@ -153,6 +507,17 @@ function SubtitleOptions({
       >
         SEGMENT
       </button>
+      {translations.map((language) => (
+        <button
+          key={language}
+          className={`bg-green-900 text-white p-2 rounded-md w-30 ${
+            type === translatedVariant(language) ? "!bg-green-500" : ""
+          }`}
+          onClick={() => setType(translatedVariant(language))}
+        >
+          {language.toUpperCase()}
+        </button>
+      ))}
       <button
         className={`bg-green-900 text-white p-2 rounded-md w-30 ${
           type === "none" ? "!bg-green-500" : ""
@@ -176,19 +541,24 @@ function DownloadButton({
   async function downloadVideo() {
     if (downloading) return;
     setDownloading(true);
-    const path = await invoke<string>("burn_subtitles", {
-      videoId,
-      subType: subtitleType,
-      videoHeight: videoRef.current?.videoHeight || 1920,
-    });
+    try {
+      const artifact = await runJob<string>("burn_subtitles", {
+        videoId,
+        subType: subtitleType,
+        videoHeight: videoRef.current?.videoHeight || 1920,
+      });
 
-    if (path) {
-      const a = document.createElement("a");
-      a.href = path;
-      a.download = "subtitled_video.mp4";
-      a.click();
+      if (artifact) {
+        const a = document.createElement("a");
+        a.href = mediaUrl(videoId, artifact);
+        a.download = "subtitled_video.mp4";
+        a.click();
+      }
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setDownloading(false);
     }
-    setDownloading(false);
   }
 
   return (
@@ -215,33 +585,42 @@ function DownloadButton({
 function SubtitlesPage() {
   const { videoId } = useStore();
   const [_, setLocation] = useLocation();
-  const [apiKey, setApiKey] = useState<string | null>(
-    localStorage.getItem("openAiApiKey")
-  );
+  // whose key the project's backend needs, null when it needs none
+  const [provider, setProvider] = useState<string | null>(null);
+  const [storedKeys, setStoredKeys] = useState<string[]>([]);
+  const [keyRequired, setKeyRequired] = useState(true);
   const [loading, setLoading] = useState(false);
   const [subtitlesExist, setSubtitlesExist] = useState(false);
   const videoRef = useRef<HTMLVideoElement>(null);
   const [showVideo, setShowVideo] = useState(true);
   const [cacheBuster, setCacheBuster] = useState(Date.now());
   const [language, setLanguage] = useState("en");
+  const [detectedLanguage, setDetectedLanguage] = useState<string | null>(
+    null
+  );
   const [subtitleType, setSubtitleType] = useState<SubtitleStyle>("none");
+  const [translations, setTranslations] = useState<string[]>([]);
+  const [translating, setTranslating] = useState(false);
 
   useEffect(() => {
     if (!videoId) {
       setLocation("/");
     }
     setCacheBuster(Date.now());
-    const storedApiKey = localStorage.getItem("openAiApiKey");
-    if (storedApiKey) {
-      setApiKey(storedApiKey);
-    }
     checkSubtitles();
+    loadTranslations();
+    migrateApiKey().catch(console.error).finally(loadApiKeys);
+    transcriptionBackend(videoId)
+      .then((backend) => {
+        setProvider(apiKeyProvider(backend));
+        setKeyRequired(backend.type === "openai");
+      })
+      .catch(console.error);
   }, [videoId]);
 
-  useEffect(() => {
-    if (!apiKey) return;
-    localStorage.setItem("openAiApiKey", apiKey);
-  }, [apiKey]);
+  function loadApiKeys() {
+    listApiKeys().then(setStoredKeys).catch(console.error);
+  }
 
   useEffect(() => {
     if (showVideo && subtitlesExist) {
@@ -252,16 +631,109 @@ function SubtitlesPage() {
   async function createSubtitles() {
     setLoading(true);
     setSubtitlesExist(false);
-    await invoke<string>("transcribe_audio", {
-      apiKey,
-      videoId,
-      language,
-    });
-    setSubtitlesExist(true);
-    setLoading(false);
+    try {
+      const outcome = await runJob<TranscriptionOutcome>("transcribe_audio", {
+        videoId,
+        // an empty language lets the backend detect it
+        language: language || null,
+      });
+      setDetectedLanguage(outcome.detected ? outcome.language : null);
+      setSubtitlesExist(true);
+      // transcribing again drops the translated tracks
+      setTranslations([]);
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
     checkSubtitles();
   }
 
+  // rebuilds the variants without calling the backend, edits are lost
+  async function regenerateSubtitles() {
+    setLoading(true);
+    try {
+      await invoke("regenerate_subtitles", { videoId });
+      setTranslations([]);
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
+    reloadVideo();
+  }
+
+  // builds the variants from an existing transcript instead of the audio
+  async function importTranscript() {
+    const selected = await open({
+      multiple: false,
+      filters: [
+        { name: "Captions", extensions: ["srt", "vtt", "sbv", "json"] },
+      ],
+    });
+    if (!selected) return;
+    setLoading(true);
+    try {
+      const outcome = await invoke<TranscriptionOutcome>("import_transcript", {
+        videoId,
+        path: selected.path,
+        language: language || null,
+      });
+      setDetectedLanguage(outcome.detected ? outcome.language : null);
+      setSubtitlesExist(true);
+      setTranslations([]);
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
+    refreshSubtitles();
+  }
+
+  // replaces the cues between `start` and `end` in every variant
+  async function retranscribeRange(start: number, end: number) {
+    setLoading(true);
+    try {
+      await runJob("retranscribe_range", {
+        videoId,
+        range: { start, end },
+      });
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setLoading(false);
+    }
+    refreshSubtitles();
+  }
+
+  // remounts the editor with the new text
+  function refreshSubtitles() {
+    setCacheBuster(Date.now());
+    reloadVideo();
+  }
+
+  async function loadTranslations() {
+    const project = await loadProject(videoId).catch(() => null);
+    setTranslations(project?.transcription?.translations ?? []);
+  }
+
+  // adds a track in `target`, timed like the segments
+  async function translateSubtitles(target: string) {
+    setTranslating(true);
+    try {
+      await runJob<Record<string, string>>("translate_subtitles", {
+        videoId,
+        languages: [target],
+      });
+      await loadTranslations();
+    } catch (error) {
+      showCommandError(error);
+    } finally {
+      setTranslating(false);
+    }
+    reloadVideo();
+  }
+
   async function checkSubtitles() {
     if (subtitlesExist) {
       setSubtitleType("words");
@@ -301,17 +773,37 @@ function SubtitlesPage() {
 
   return (
     <div className="container">
-      <ApiKeyComponent apiKey={apiKey} setApiKey={setApiKey} />
+      {provider && (
+        <ApiKeyComponent
+          provider={provider}
+          stored={storedKeys.includes(provider)}
+          onChange={loadApiKeys}
+        />
+      )}
       <GenerateSubtitlesButton
         createSubtitles={createSubtitles}
+        regenerateSubtitles={regenerateSubtitles}
+        importTranscript={importTranscript}
         loading={loading}
-        apiKey={apiKey}
+        hasApiKey={provider !== null && storedKeys.includes(provider)}
+        keyRequired={keyRequired}
         subtitlesExist={subtitlesExist}
       />
       <LanguageSelect
         selectedLanguage={language}
         setSelectedLanguage={setLanguage}
       />
+      <GlossaryEditor videoId={videoId} />
+      <SpeakersEditor
+        videoId={videoId}
+        subtitlesExist={subtitlesExist}
+        refreshSubtitles={refreshSubtitles}
+      />
+      {detectedLanguage && (
+        <p className="text-center text-gray-500">
+          Detected language: {detectedLanguage}
+        </p>
+      )}
       <div className="flex justify-center items-center space-x-4 pt-4">
         <div className="flex justify-center flex-col">
           {showVideo && (
@@ -325,46 +817,62 @@ function SubtitlesPage() {
               preload="metadata"
               ref={videoRef}
               className="mx-auto"
+              crossOrigin="anonymous"
             >
-              <source src={`./${videoId}_trimmed.mp4?${cacheBuster}`} type="video/mp4" />
+              <source
+                src={`${mediaUrl(videoId, `${videoId}_trimmed.mp4`)}?${cacheBuster}`}
+                type="video/mp4"
+              />
               {subtitlesExist && (
                 <>
                   <track
                     label="words"
                     kind="subtitles"
                     srcLang="en"
-                    src={`./${videoId}_words.vtt`}
+                    src={mediaUrl(videoId, `${videoId}_words.vtt`)}
                   />
                   <track
                     label="3words"
                     kind="subtitles"
                     srcLang="en"
-                    src={`./${videoId}_3words.vtt`}
+                    src={mediaUrl(videoId, `${videoId}_3words.vtt`)}
                   />
                   <track
                     label="4words"
                     kind="subtitles"
                     srcLang="en"
-                    src={`./${videoId}_4words.vtt`}
+                    src={mediaUrl(videoId, `${videoId}_4words.vtt`)}
                   />
                   <track
                     label="5words"
                     kind="subtitles"
                     srcLang="en"
-                    src={`./${videoId}_5words.vtt`}
+                    src={mediaUrl(videoId, `${videoId}_5words.vtt`)}
                   />
                   <track
                     label="6words"
                     kind="subtitles"
                     srcLang="en"
-                    src={`./${videoId}_6words.vtt`}
+                    src={mediaUrl(videoId, `${videoId}_6words.vtt`)}
                   />
                   <track
                     label="segments"
                     kind="subtitles"
                     // srcLang="en"
-                    src={`./${videoId}.vtt`}
+                    src={mediaUrl(videoId, `${videoId}.vtt`)}
                   />
+                  {translations.map((language) => (
+                    <track
+                      key={language}
+                      label={translatedVariant(language)}
+                      kind="subtitles"
+                      srcLang={language}
+                      src={mediaUrl(
+                        videoId,
+                        `${videoId}_${translatedVariant(language)}.vtt`
+                      )}
+                    />
+                  ))}
                 </>
               )}
             </video>
@@ -373,6 +881,18 @@ function SubtitlesPage() {
             type={subtitleType}
             setType={enableSubtitle}
             subtitlesExist={subtitlesExist}
+            translations={translations}
+          />
+          <RetranscribeRange
+            retranscribeRange={retranscribeRange}
+            loading={loading}
+            subtitlesExist={subtitlesExist}
+            videoRef={videoRef}
+          />
+          <TranslateSubtitles
+            translateSubtitles={translateSubtitles}
+            translating={translating}
+            subtitlesExist={subtitlesExist}
           />
           <DownloadButton
             subtitlesExist={subtitlesExist}
@@ -382,7 +902,11 @@ function SubtitlesPage() {
           />
         </div>
         {subtitlesExist && (
-          <VttTextArea type={subtitleType} reloadVideo={reloadVideo} />
+          <VttTextArea
+            key={cacheBuster}
+            type={subtitleType}
+            reloadVideo={reloadVideo}
+          />
         )}
       </div>
     </div>
@@ -0,0 +1,112 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type Source =
+  | { type: "unknown" }
+  | { type: "youtube"; url: string }
+  | { type: "upload"; path: string };
+
+export type Timing = { start: number; end: number };
+
+export type Dimensions = { width: number; height: number; x: number; y: number };
+
+export type Render = {
+  output: string;
+  subtitleVariant: string;
+  videoHeight: number;
+  createdAt: number;
+};
+
+// mirrors `HttpOptions` in src-tauri/core/src/transcription/openai.rs,
+// defaults to a 600s timeout and 4 retries, a timeout of 0 is refused
+export type HttpOptions = {
+  timeoutSecs?: number;
+  maxRetries?: number;
+};
+
+// mirrors `BackendConfig` in src-tauri/core/src/transcription/mod.rs
+export type TranscriptionBackend =
+  | ({ type: "openai"; model?: string | null } & HttpOptions)
+  | ({
+      type: "openaiCompatible";
+      baseUrl: string;
+      model: string;
+      headers?: Record<string, string>;
+    } & HttpOptions)
+  | { type: "command"; program: string; args?: string[] }
+  // only in builds with the `whisper` feature
+  | { type: "whisper"; modelPath: string; threads?: number | null };
+
+// mirrors `Term` in src-tauri/core/src/transcription/glossary.rs
+export type GlossaryTerm = {
+  term: string;
+  // misspellings replaced by `term`
+  replace?: string[];
+};
+
+// mirrors `project.json`, see src-tauri/core/src/storage/project.rs
+export type Project = {
+  id: string;
+  source: Source;
+  title: string | null;
+  // YouTube channel id, its videos share learned corrections
+  channel: string | null;
+  createdAt: number;
+  updatedAt: number;
+  timings: Timing[];
+  crop: Dimensions | null;
+  transcription: {
+    language: string | null;
+    model: string | null;
+    backend: TranscriptionBackend | null;
+    // cache key of the response the subtitles were built from
+    response: string | null;
+    // languages with a translated subtitle track
+    translations: string[];
+    // added to the global glossary from the settings
+    glossary: GlossaryTerm[];
+  } | null;
+  subtitleVariant: string | null;
+  style: unknown;
+  // speaker names by the label the diarizer gave them
+  speakers: Record<string, string>;
+  renders: Render[];
+};
+
+export function listProjects() {
+  return invoke<Project[]>("list_projects");
+}
+
+export function loadProject(videoId: string) {
+  return invoke<Project>("load_project", { videoId });
+}
+
+export function saveProject(project: Project) {
+  return invoke<Project>("save_project", { project });
+}
+
+// replaces the project's glossary, used by the next transcription
+export async function saveGlossary(videoId: string, glossary: GlossaryTerm[]) {
+  const project = await loadProject(videoId);
+  project.transcription = {
+    language: null,
+    model: null,
+    backend: null,
+    response: null,
+    translations: [],
+    ...project.transcription,
+    glossary,
+  };
+  return saveProject(project);
+}
+
+// the backend `transcribe_audio` will use: the project's, then the app default
+export async function transcriptionBackend(videoId: string) {
+  const [project, settings] = await Promise.all([
+    loadProject(videoId).catch(() => null),
+    invoke<{ transcription_backend: TranscriptionBackend | null }>("get_settings"),
+  ]);
+  return (
+    project?.transcription?.backend ??
+    settings.transcription_backend ?? { type: "openai" }
+  );
+}